@@ -1,7 +1,7 @@
 use chrono::Utc;
 use std::fs::{create_dir_all, OpenOptions};
 use std::io::Write;
-use std::path::Path;
+use std::path::PathBuf;
 
 pub struct ErrorLogger {
     log_dir: String,
@@ -9,7 +9,11 @@ pub struct ErrorLogger {
 
 impl ErrorLogger {
     pub fn new() -> Self {
-        let log_dir = "logs/errors";
+        Self::with_dir("logs/errors")
+    }
+
+    /// Logger that writes into `log_dir`, creating it if needed.
+    pub fn with_dir(log_dir: &str) -> Self {
         let _ = create_dir_all(log_dir);
 
         Self {
@@ -28,11 +32,7 @@ impl ErrorLogger {
             context_str
         );
 
-        let filename = format!(
-            "{}/error_{}.log",
-            self.log_dir,
-            log_type.to_string().to_lowercase()
-        );
+        let filename = self.current_log_path(log_type);
 
         if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&filename) {
             let _ = writeln!(file, "{}", log_entry);
@@ -40,6 +40,12 @@ impl ErrorLogger {
         eprintln!("{}", log_entry);
     }
 
+    /// File that errors of `log_type` are appended to.
+    pub fn current_log_path(&self, log_type: super::LogType) -> PathBuf {
+        PathBuf::from(&self.log_dir)
+            .join(format!("error_{}.log", log_type.to_string().to_lowercase()))
+    }
+
     #[allow(dead_code)]
     pub async fn get_logs(
         &self,
@@ -50,12 +56,8 @@ impl ErrorLogger {
         let mut all_logs = Vec::new();
 
         if let Some(log_type) = filter_type {
-            let filename = format!(
-                "{}/error_{}.log",
-                self.log_dir,
-                log_type.to_string().to_lowercase()
-            );
-            if Path::new(&filename).exists() {
+            let filename = self.current_log_path(log_type);
+            if filename.exists() {
                 let content = fs::read_to_string(&filename)?;
                 all_logs.extend(content.lines().map(|s| s.to_string()));
             }
@@ -86,3 +88,25 @@ impl Default for ErrorLogger {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::logger::LogType;
+
+    #[tokio::test]
+    async fn current_log_path_points_at_the_written_log() {
+        let log_dir = std::env::temp_dir().join(format!("exia-logs-{}", std::process::id()));
+        let logger = ErrorLogger::with_dir(log_dir.to_str().unwrap());
+
+        logger
+            .log(LogType::NetworkScanner, "ARP channel failed", None)
+            .await;
+        let log_path = logger.current_log_path(LogType::NetworkScanner);
+        let content = std::fs::read_to_string(&log_path);
+        let _ = std::fs::remove_dir_all(&log_dir);
+
+        assert_eq!(log_path, log_dir.join("error_network_scanner.log"));
+        assert!(content.unwrap().contains("ERROR: ARP channel failed"));
+    }
+}
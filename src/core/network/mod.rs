@@ -1,2 +1 @@
 pub mod scanner;
-pub mod types;
@@ -0,0 +1,188 @@
+use super::types::{normalize_mac, LocalNetworkDevice};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanDiff {
+    pub added: Vec<LocalNetworkDevice>,
+    pub removed: Vec<LocalNetworkDevice>,
+    pub changed: Vec<DeviceChange>,
+}
+
+/// A device present in both scans whose details differ.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct DeviceChange {
+    /// The device as seen in the current scan.
+    pub device: LocalNetworkDevice,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub previous: Option<String>,
+    pub current: Option<String>,
+}
+
+/// Compares two scans. Devices are matched by MAC address rather than IP, since DHCP
+/// reassigns IPs; an IP change therefore shows up as a change, not a removal plus addition.
+#[allow(dead_code)]
+pub fn diff_scans(previous: &[LocalNetworkDevice], current: &[LocalNetworkDevice]) -> ScanDiff {
+    let previous_by_mac: HashMap<String, &LocalNetworkDevice> = previous
+        .iter()
+        .map(|device| (normalize_mac(&device.mac_address), device))
+        .collect();
+    let current_by_mac: HashMap<String, &LocalNetworkDevice> = current
+        .iter()
+        .map(|device| (normalize_mac(&device.mac_address), device))
+        .collect();
+
+    let mut diff = ScanDiff::default();
+
+    for device in current {
+        match previous_by_mac.get(&normalize_mac(&device.mac_address)) {
+            Some(previous_device) => {
+                let changes = field_changes(previous_device, device);
+                if !changes.is_empty() {
+                    diff.changed.push(DeviceChange {
+                        device: device.clone(),
+                        changes,
+                    });
+                }
+            }
+            None => diff.added.push(device.clone()),
+        }
+    }
+
+    diff.removed = previous
+        .iter()
+        .filter(|device| !current_by_mac.contains_key(&normalize_mac(&device.mac_address)))
+        .cloned()
+        .collect();
+
+    diff
+}
+
+fn field_changes(previous: &LocalNetworkDevice, current: &LocalNetworkDevice) -> Vec<FieldChange> {
+    let fields = [
+        (
+            "ip_address",
+            Some(previous.ip_address.clone()),
+            Some(current.ip_address.clone()),
+        ),
+        (
+            "ipv6_address",
+            previous.ipv6_address.clone(),
+            current.ipv6_address.clone(),
+        ),
+        (
+            "hostname",
+            previous.hostname.clone(),
+            current.hostname.clone(),
+        ),
+        (
+            "device_name",
+            previous.device_name.clone(),
+            current.device_name.clone(),
+        ),
+        ("vendor", previous.vendor.clone(), current.vendor.clone()),
+        (
+            "device_class",
+            Some(previous.device_class.to_string()),
+            Some(current.device_class.to_string()),
+        ),
+        (
+            "open_ports",
+            previous
+                .open_ports
+                .as_ref()
+                .map(|ports| format_ports(ports)),
+            current.open_ports.as_ref().map(|ports| format_ports(ports)),
+        ),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, previous, current)| previous != current)
+        .map(|(field, previous, current)| FieldChange {
+            field,
+            previous,
+            current,
+        })
+        .collect()
+}
+
+fn format_ports(ports: &[u16]) -> String {
+    ports
+        .iter()
+        .map(|port| port.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::network::scanner::types::test_device;
+
+    #[test]
+    fn new_mac_is_added() {
+        let previous = [test_device("192.168.1.10", "d8:be:65:00:00:01")];
+        let current = [
+            test_device("192.168.1.10", "d8:be:65:00:00:01"),
+            test_device("192.168.1.11", "cc:40:85:d1:4e:94"),
+        ];
+
+        let diff = diff_scans(&previous, &current);
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].mac_address, "cc:40:85:d1:4e:94");
+        assert!(diff.removed.is_empty());
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn missing_mac_is_removed() {
+        let previous = [
+            test_device("192.168.1.10", "d8:be:65:00:00:01"),
+            test_device("192.168.1.11", "cc:40:85:d1:4e:94"),
+        ];
+        let current = [test_device("192.168.1.10", "D8-BE-65-00-00-01")];
+
+        let diff = diff_scans(&previous, &current);
+
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].mac_address, "cc:40:85:d1:4e:94");
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn new_ip_for_the_same_mac_is_a_change() {
+        let previous = [test_device("192.168.1.10", "d8:be:65:00:00:01")];
+        let mut renewed = test_device("192.168.1.42", "d8:be:65:00:00:01");
+        renewed.hostname = Some("tv.lan".to_string());
+
+        let diff = diff_scans(&previous, &[renewed]);
+
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].device.ip_address, "192.168.1.42");
+        assert_eq!(
+            diff.changed[0].changes,
+            vec![
+                FieldChange {
+                    field: "ip_address",
+                    previous: Some("192.168.1.10".to_string()),
+                    current: Some("192.168.1.42".to_string()),
+                },
+                FieldChange {
+                    field: "hostname",
+                    previous: None,
+                    current: Some("tv.lan".to_string()),
+                },
+            ]
+        );
+    }
+}
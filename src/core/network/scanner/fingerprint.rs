@@ -0,0 +1,249 @@
+use super::types::{DeviceClass, DeviceSignals, LocalNetworkDevice};
+
+/// Sources that can name a device, ordered by confidence (lowest first).
+/// When two sources disagree, the one that sorts later wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NameSource {
+    /// Advertised over mDNS, but often a truncated or generic host label.
+    Mdns,
+    /// Reported by the device's own HTTP/UPnP endpoint.
+    SmartDevice,
+    /// Configured by the user in `device_config.json`; always trusted.
+    UserMapping,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceFingerprint {
+    pub name: Option<String>,
+    pub hostname: Option<String>,
+    pub vendor: Option<String>,
+    pub device_class: DeviceClass,
+}
+
+/// Resolves the signals collected for a device into a single best identity.
+///
+/// - name: user mapping > smart-device endpoint > mDNS (reverse DNS is never used)
+/// - hostname: reverse DNS > mDNS
+/// - vendor: MAC OUI, unless unknown or randomized, then mDNS services
+/// - class: mapped device type > mDNS services > device names > OUI vendor
+pub fn fingerprint(device: &LocalNetworkDevice) -> DeviceFingerprint {
+    let signals = &device.signals;
+    let service_types = device.mdns_service_types.as_deref().unwrap_or_default();
+
+    DeviceFingerprint {
+        name: best_name(signals),
+        hostname: best_hostname(signals),
+        vendor: best_vendor(signals, service_types),
+        device_class: best_class(signals, service_types),
+    }
+}
+
+fn best_name(signals: &DeviceSignals) -> Option<String> {
+    // PTR records name the DHCP lease rather than the device, so DNS never names it.
+    let candidates = [
+        (NameSource::Mdns, &signals.mdns_name),
+        (NameSource::SmartDevice, &signals.smart_device_name),
+        (NameSource::UserMapping, &signals.mapped_name),
+    ];
+
+    candidates
+        .into_iter()
+        .filter_map(|(source, value)| non_empty(value).map(|name| (source, name)))
+        .max_by_key(|(source, _)| *source)
+        .map(|(_, name)| name.to_string())
+}
+
+fn best_hostname(signals: &DeviceSignals) -> Option<String> {
+    non_empty(&signals.reverse_dns_hostname)
+        .or_else(|| non_empty(&signals.mdns_name))
+        .map(|hostname| hostname.to_string())
+}
+
+fn best_vendor(signals: &DeviceSignals, service_types: &[String]) -> Option<String> {
+    let oui_vendor = non_empty(&signals.oui_vendor);
+
+    // Randomized (locally administered) or unknown MACs carry no vendor information,
+    // so fall back to what the device advertises over mDNS.
+    let oui_is_informative =
+        oui_vendor.is_some_and(|vendor| vendor != "Unknown Vendor" && vendor != "Local Admin");
+    if oui_is_informative {
+        return oui_vendor.map(|vendor| vendor.to_string());
+    }
+
+    vendor_from_services(service_types)
+        .map(|vendor| vendor.to_string())
+        .or_else(|| oui_vendor.map(|vendor| vendor.to_string()))
+}
+
+fn best_class(signals: &DeviceSignals, service_types: &[String]) -> DeviceClass {
+    let text_signals = [
+        non_empty(&signals.mapped_device_type),
+        non_empty(&signals.smart_device_name),
+        non_empty(&signals.mdns_name),
+    ];
+
+    if let Some(class) = text_signals[0].and_then(classify_text) {
+        return class;
+    }
+    if let Some(class) = class_from_services(service_types) {
+        return class;
+    }
+
+    text_signals[1..]
+        .iter()
+        .filter_map(|text| text.and_then(classify_text))
+        .next()
+        .or_else(|| non_empty(&signals.oui_vendor).and_then(classify_text))
+        .unwrap_or_default()
+}
+
+fn classify_text(text: &str) -> Option<DeviceClass> {
+    let text = text.to_lowercase();
+    let has_any = |keywords: &[&str]| keywords.iter().any(|keyword| text.contains(keyword));
+
+    // Hubs first, so "Hue Bridge" is not mistaken for one of the bulbs it controls.
+    if has_any(&["hub", "bridge", "homekit", "smartthings"]) {
+        Some(DeviceClass::Hub)
+    } else if has_any(&["bulb", "light", "lamp", "hue", "lifx"]) {
+        Some(DeviceClass::Light)
+    } else if has_any(&["camera", "doorbell", "arlo", "wyze"]) {
+        Some(DeviceClass::Camera)
+    } else if has_any(&[
+        "speaker",
+        "sonos",
+        "roku",
+        "chromecast",
+        "television",
+        "media",
+    ]) {
+        Some(DeviceClass::MediaPlayer)
+    } else if has_any(&[
+        "router", "netgear", "ubiquiti", "unifi", "eero", "cisco", "linksys",
+    ]) {
+        Some(DeviceClass::NetworkEquipment)
+    } else if has_any(&[
+        "computer",
+        "laptop",
+        "desktop",
+        "macbook",
+        "raspberry",
+        "workstation",
+    ]) {
+        Some(DeviceClass::Computer)
+    } else {
+        None
+    }
+}
+
+fn class_from_services(service_types: &[String]) -> Option<DeviceClass> {
+    let has = |prefix: &str| {
+        service_types
+            .iter()
+            .any(|service| service.starts_with(prefix))
+    };
+
+    if has("_googlecast.") || has("_airplay.") {
+        Some(DeviceClass::MediaPlayer)
+    } else if has("_homekit.") {
+        Some(DeviceClass::Hub)
+    } else if has("_workstation.") {
+        Some(DeviceClass::Computer)
+    } else {
+        None
+    }
+}
+
+fn vendor_from_services(service_types: &[String]) -> Option<&'static str> {
+    let has = |prefix: &str| {
+        service_types
+            .iter()
+            .any(|service| service.starts_with(prefix))
+    };
+
+    if has("_googlecast.") {
+        Some("Google/Nest")
+    } else if has("_airplay.") || has("_homekit.") {
+        Some("Apple Device")
+    } else {
+        None
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device_with(signals: DeviceSignals) -> LocalNetworkDevice {
+        LocalNetworkDevice {
+            signals,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn user_mapping_name_beats_mdns_name() {
+        let device = device_with(DeviceSignals {
+            mdns_name: Some("Living-Room".to_string()),
+            mapped_name: Some("Desk Lamp (Office)".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            fingerprint(&device).name.as_deref(),
+            Some("Desk Lamp (Office)")
+        );
+    }
+
+    #[test]
+    fn reverse_dns_hostname_beats_mdns_hostname() {
+        let device = device_with(DeviceSignals {
+            reverse_dns_hostname: Some("printer.lan".to_string()),
+            mdns_name: Some("Printer".to_string()),
+            ..Default::default()
+        });
+
+        let fingerprint = fingerprint(&device);
+        assert_eq!(fingerprint.hostname.as_deref(), Some("printer.lan"));
+        // Reverse DNS never names the device.
+        assert_eq!(fingerprint.name.as_deref(), Some("Printer"));
+    }
+
+    #[test]
+    fn randomized_mac_vendor_falls_back_to_mdns_services() {
+        let mut device = device_with(DeviceSignals {
+            oui_vendor: Some("Local Admin".to_string()),
+            ..Default::default()
+        });
+        device.mdns_service_types = Some(vec!["_airplay._tcp.local".to_string()]);
+
+        let fingerprint = fingerprint(&device);
+        assert_eq!(fingerprint.vendor.as_deref(), Some("Apple Device"));
+        assert_eq!(fingerprint.device_class, DeviceClass::MediaPlayer);
+    }
+
+    #[test]
+    fn hue_bridge_is_a_hub_not_a_light() {
+        let device = device_with(DeviceSignals {
+            smart_device_name: Some("Hue Bridge".to_string()),
+            oui_vendor: Some("Philips Hue/Smart Lighting".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(fingerprint(&device).device_class, DeviceClass::Hub);
+    }
+
+    #[test]
+    fn mapped_device_type_beats_mdns_services() {
+        let mut device = device_with(DeviceSignals {
+            mapped_device_type: Some("camera".to_string()),
+            ..Default::default()
+        });
+        device.mdns_service_types = Some(vec!["_googlecast._tcp.local".to_string()]);
+
+        assert_eq!(fingerprint(&device).device_class, DeviceClass::Camera);
+    }
+}
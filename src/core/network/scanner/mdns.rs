@@ -1,93 +1,155 @@
 use crate::core::network::scanner::{
-    dns::extract_ip_from_hostname, types::MDNS_SERVICES, utils::extract_device_name_from_mdns,
+    dns::extract_ip_from_hostname, types::MdnsConfig, utils::extract_device_name_from_mdns,
 };
 use futures::future::join_all;
 use futures_util::{pin_mut, StreamExt};
 use std::collections::HashMap;
+use std::future::Future;
 use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::Semaphore;
 use tokio::time::timeout;
 
 pub async fn discover_mdns_devices(
-    _timeout_duration: Duration,
+    timeout_duration: Duration,
+    config: &MdnsConfig,
 ) -> HashMap<IpAddr, (String, Vec<String>)> {
-    let mdns_tasks: Vec<_> = MDNS_SERVICES
-        .iter()
-        .map(|&service_name| {
-            tokio::spawn(async move {
-                let mut service_devices = HashMap::new();
-
-                let discovery = match mdns::discover::all(service_name, Duration::from_millis(500))
-                {
-                    Ok(discovery) => discovery,
-                    Err(_) => return service_devices,
-                };
-
-                let mdns_stream = discovery.listen();
-                pin_mut!(mdns_stream);
-
-                let _timeout_result = timeout(Duration::from_millis(500), async {
-                    while let Some(response) = mdns_stream.next().await {
-                        match response {
-                            Ok(response) => {
-                                let mut device_name: Option<String> = None;
-                                let mut device_ip: Option<IpAddr> = None;
-
-                                for record in response.records() {
-                                    match &record.kind {
-                                        mdns::RecordKind::A(addr) => {
-                                            device_ip = Some(IpAddr::V4(*addr));
-                                        }
-                                        mdns::RecordKind::AAAA(addr) => {
-                                            device_ip = Some(IpAddr::V6(*addr));
-                                        }
-                                        _ => {}
-                                    }
-
-                                    let hostname = record.name.to_string();
-                                    if let Some(extracted_ip) = extract_ip_from_hostname(&hostname)
-                                    {
-                                        device_ip = Some(extracted_ip);
-                                    }
-                                    if device_name.is_none() && !hostname.is_empty() {
-                                        let cleaned_name = extract_device_name_from_mdns(&hostname);
-                                        device_name = Some(cleaned_name);
-                                    }
-                                }
-
-                                if let (Some(name), Some(ip)) = (device_name, device_ip) {
-                                    service_devices
-                                        .entry(ip)
-                                        .or_insert_with(|| (name.clone(), Vec::new()))
-                                        .1
-                                        .push(service_name.to_string());
-                                }
-                            }
-                            Err(_) => {}
-                        }
-                    }
-                })
-                .await;
+    let mdns_results = query_services(
+        config.services(),
+        config.max_concurrent_queries,
+        move |service_name| query_service(service_name, timeout_duration),
+    )
+    .await;
+
+    let mut discovered_devices: HashMap<IpAddr, (String, Vec<String>)> = HashMap::new();
+    for service_devices in mdns_results {
+        for (ip, (name, mut services)) in service_devices {
+            discovered_devices
+                .entry(ip)
+                .and_modify(|entry| entry.1.append(&mut services))
+                .or_insert((name, services));
+        }
+    }
+
+    discovered_devices
+}
+
+/// Runs `query` for every service on its own task, at most `max_concurrent` at a time.
+/// Each query holds its own socket, so a long list of extra services is not opened at once.
+async fn query_services<F, Fut, T>(services: Vec<String>, max_concurrent: usize, query: F) -> Vec<T>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let limit = Arc::new(Semaphore::new(max_concurrent.max(1)));
 
-                service_devices
+    let tasks: Vec<_> = services
+        .into_iter()
+        .map(|service_name| {
+            let limit = limit.clone();
+            let query = query(service_name);
+            tokio::spawn(async move {
+                let _permit = limit.acquire().await.ok()?;
+                Some(query.await)
             })
         })
         .collect();
 
-    let mdns_results = join_all(mdns_tasks).await;
-    let mut discovered_devices: HashMap<IpAddr, (String, Vec<String>)> = HashMap::new();
-    for task_result in mdns_results {
-        if let Ok(service_devices) = task_result {
-            for (ip, (name, mut services)) in service_devices {
-                discovered_devices
-                    .entry(ip)
-                    .and_modify(|entry| entry.1.append(&mut services))
-                    .or_insert((name, services));
+    join_all(tasks)
+        .await
+        .into_iter()
+        .filter_map(|result| result.ok().flatten())
+        .collect()
+}
+
+async fn query_service(
+    service_name: String,
+    timeout_duration: Duration,
+) -> HashMap<IpAddr, (String, Vec<String>)> {
+    let mut service_devices = HashMap::new();
+
+    let discovery = match mdns::discover::all(&service_name, timeout_duration) {
+        Ok(discovery) => discovery,
+        Err(_) => return service_devices,
+    };
+
+    let mdns_stream = discovery.listen();
+    pin_mut!(mdns_stream);
+
+    let _timeout_result = timeout(timeout_duration, async {
+        while let Some(response) = mdns_stream.next().await {
+            match response {
+                Ok(response) => {
+                    let mut device_name: Option<String> = None;
+                    let mut device_ip: Option<IpAddr> = None;
+
+                    for record in response.records() {
+                        match &record.kind {
+                            mdns::RecordKind::A(addr) => {
+                                device_ip = Some(IpAddr::V4(*addr));
+                            }
+                            mdns::RecordKind::AAAA(addr) => {
+                                device_ip = Some(IpAddr::V6(*addr));
+                            }
+                            _ => {}
+                        }
+
+                        let hostname = record.name.to_string();
+                        if let Some(extracted_ip) = extract_ip_from_hostname(&hostname) {
+                            device_ip = Some(extracted_ip);
+                        }
+                        if device_name.is_none() && !hostname.is_empty() {
+                            let cleaned_name = extract_device_name_from_mdns(&hostname);
+                            device_name = Some(cleaned_name);
+                        }
+                    }
+
+                    if let (Some(name), Some(ip)) = (device_name, device_ip) {
+                        service_devices
+                            .entry(ip)
+                            .or_insert_with(|| (name.clone(), Vec::new()))
+                            .1
+                            .push(service_name.clone());
+                    }
+                }
+                Err(_) => {}
             }
         }
-    }
+    })
+    .await;
 
-    if !discovered_devices.is_empty() {}
+    service_devices
+}
 
-    discovered_devices
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn concurrent_queries_stay_within_the_limit() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let services: Vec<String> = (0..20)
+            .map(|i| format!("_service{}._tcp.local", i))
+            .collect();
+
+        let results = query_services(services, 3, |service_name| {
+            let running = running.clone();
+            let peak = peak.clone();
+            async move {
+                let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now_running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                service_name
+            }
+        })
+        .await;
+
+        assert_eq!(results.len(), 20);
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+    }
 }
@@ -1,7 +1,12 @@
 pub mod bulb_control;
+pub mod diff;
 pub mod dns;
+pub mod fingerprint;
 pub mod mdns;
+pub mod ndp;
 pub mod network;
+pub mod ports;
+pub mod scheduler;
 pub mod smart_devices;
 pub mod types;
 pub mod utils;
@@ -9,13 +14,20 @@ pub mod vendor;
 
 use crate::core::logger::{log_error, LogType};
 use dns::perform_reverse_dns_lookup;
+use fingerprint::fingerprint;
 use mdns::discover_mdns_devices;
+use ndp::perform_ndp_scan;
 use network::{get_default_gateway, scan_local_network_interfaces};
+use ports::scan_ports_limited;
 use smart_devices::discover_smart_device_name;
-use types::{DeviceMapping, LocalNetworkDevice};
-use vendor::get_vendor_from_mac;
+use types::{
+    DeviceMapping, DeviceSignals, LocalNetworkDevice, ScanConfig, ScanControl, DISCOVERY_PORTS,
+};
+use vendor::{VendorDb, VendorMapping};
 
+use futures::channel::mpsc::{self, UnboundedSender};
 use futures::future::join_all;
+use futures::Stream;
 use ipnet::IpNet;
 use pnet::datalink::{self, Channel};
 use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket};
@@ -23,18 +35,109 @@ use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
 use pnet::packet::{MutablePacket, Packet};
 use pnet::util::MacAddr;
 use regex;
-use std::collections::HashMap;
-use std::net::{IpAddr, Ipv4Addr};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::process::Command;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-pub async fn scan_local_network_devices() -> Vec<LocalNetworkDevice> {
+pub async fn scan_local_network_devices(config: &ScanConfig) -> Vec<LocalNetworkDevice> {
+    scan_local_network_devices_with_control(config, ScanControl::default()).await
+}
+
+/// Same as `scan_local_network_devices`, but the ARP scan can be paused and resumed
+/// through `control` while it runs.
+pub async fn scan_local_network_devices_with_control(
+    config: &ScanConfig,
+    control: ScanControl,
+) -> Vec<LocalNetworkDevice> {
+    scan_devices(config, control, None)
+        .await
+        .unwrap_or_default()
+}
+
+/// Blocking wrapper around `scan_local_network_devices` for callers without a tokio runtime.
+/// Starts its own runtime, so it must not be called from async code.
+#[allow(dead_code)]
+pub fn scan_local_network_devices_blocking(
+    config: &ScanConfig,
+) -> Result<Vec<LocalNetworkDevice>, std::io::Error> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    Ok(runtime.block_on(scan_local_network_devices(config)))
+}
+
+/// Yields each device as soon as it answers the ARP/NDP sweep or is read from the system
+/// ARP table. Devices are emitted once, identified from their OUI vendor and the device
+/// mapping only: the stream ends after discovery, without the mDNS/DNS/HTTP name resolution
+/// and port scan that `scan_local_network_devices` runs. Cancelling `cancellation` stops the
+/// scan and ends the stream.
+///
+/// The scan runs on a spawned task, so this must be called from within a tokio runtime;
+/// like `tokio::spawn`, it panics otherwise.
+#[allow(dead_code)]
+pub fn scan_local_network_devices_stream(
+    config: ScanConfig,
+    cancellation: CancellationToken,
+) -> impl Stream<Item = LocalNetworkDevice> {
+    let control = ScanControl::with_cancellation(cancellation.clone());
+    spawn_device_stream(cancellation, move |found| async move {
+        scan_devices(&config, control, Some(found)).await;
+    })
+}
+
+/// Runs the future returned by `scan` on a spawned task and streams the devices it sends.
+/// The stream ends once the scan finishes or `cancellation` fires, since either way every
+/// sender is dropped.
+fn spawn_device_stream<F, Fut>(
+    cancellation: CancellationToken,
+    scan: F,
+) -> impl Stream<Item = LocalNetworkDevice>
+where
+    F: FnOnce(UnboundedSender<LocalNetworkDevice>) -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let (found_tx, found_rx) = mpsc::unbounded();
+    let scan = scan(found_tx);
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = cancellation.cancelled() => {}
+            _ = scan => {}
+        }
+    });
+
+    found_rx
+}
+
+/// Returns `None` when the scan could not run at all (no default gateway, no usable
+/// interface, no datalink channel), so callers can tell a failed scan from an empty network.
+async fn scan_devices(
+    config: &ScanConfig,
+    control: ScanControl,
+    found: Option<UnboundedSender<LocalNetworkDevice>>,
+) -> Option<Vec<LocalNetworkDevice>> {
     let start_time = Instant::now();
 
     let mut devices: HashMap<String, LocalNetworkDevice> = HashMap::new();
     let device_mapping = DeviceMapping::load_from_file("device_config.json").ok();
+    let mut vendors = VendorMapping::load_from_file("vendor_config.json").unwrap_or_default();
+    if let Some(path) = &config.oui_database {
+        match VendorDb::load_from_file(path) {
+            Ok(database) => vendors = vendors.with_database(database),
+            Err(e) => {
+                log_error(
+                    LogType::NetworkScanner,
+                    "Failed to load OUI database",
+                    Some(&format!("{}: {}", path, e)),
+                )
+                .await;
+            }
+        }
+    }
+    let vendors = Arc::new(vendors);
 
     let gateway_info = match get_default_gateway() {
         Ok(gateway) => gateway,
@@ -45,7 +148,7 @@ pub async fn scan_local_network_devices() -> Vec<LocalNetworkDevice> {
                 Some(&e.to_string()),
             )
             .await;
-            return devices.into_values().collect();
+            return None;
         }
     };
 
@@ -68,24 +171,26 @@ pub async fn scan_local_network_devices() -> Vec<LocalNetworkDevice> {
 
     match network_interface_with_gateway_info {
         Some(iface_info) => {
-            let Some(pnet_iface) = iface_info.pnet_interface_ref else {
-                return devices.into_values().collect();
-            };
+            let pnet_iface = iface_info.pnet_interface_ref?;
 
-            let Some(my_ip_info) = pnet_iface.ips.iter().find(|ip_info| ip_info.is_ipv4()) else {
-                return devices.into_values().collect();
-            };
+            let my_ip_info = pnet_iface.ips.iter().find(|ip_info| ip_info.is_ipv4())?;
             let source_ip = match my_ip_info.ip() {
                 IpAddr::V4(ipv4) => ipv4,
-                _ => return devices.into_values().collect(),
+                _ => return None,
             };
 
-            let Some(source_mac) = pnet_iface.mac else {
-                return devices.into_values().collect();
-            };
+            let source_mac = pnet_iface.mac?;
 
             let Ok(cidr) = format!("{}/{}", source_ip, my_ip_info.prefix()).parse::<IpNet>() else {
-                return devices.into_values().collect();
+                return None;
+            };
+
+            let scan_range = match config.scan_range(cidr) {
+                Ok(scan_range) => scan_range,
+                Err(e) => {
+                    log_error(LogType::NetworkScanner, "Ignoring subnet_filter", Some(&e)).await;
+                    cidr
+                }
             };
 
             // Debug: Show what we're scanning
@@ -93,20 +198,10 @@ pub async fn scan_local_network_devices() -> Vec<LocalNetworkDevice> {
             // println!("🔍 Debug: Network CIDR: {}", cidr);
             // println!("🔍 Debug: Network range: {} to {}", cidr.network(), cidr.broadcast());
 
-            let target_ips: Vec<Ipv4Addr> = cidr
-                .hosts()
-                .filter_map(|ip| {
-                    if let IpAddr::V4(ipv4) = ip {
-                        if ipv4 != source_ip {
-                            Some(ipv4)
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+            // The system ARP table is read first so hosts it already maps to an excluded
+            // MAC are never swept. It also covers devices that ignore active requests.
+            let arp_table_devices = read_arp_table(scan_range, &vendors).await;
+            let target_ips = sweep_targets(&scan_range, source_ip, config, &arp_table_devices);
 
             // println!("🔍 Debug: Scanning {} target IPs", target_ips.len());
 
@@ -123,55 +218,101 @@ pub async fn scan_local_network_devices() -> Vec<LocalNetworkDevice> {
             //     }
             // }
 
-            let arp_future =
-                perform_optimized_arp_scan(&pnet_iface, source_ip, source_mac, target_ips);
-
-            let mdns_future = discover_mdns_devices(Duration::from_millis(400));
+            let record_device = {
+                let vendors = vendors.clone();
+                let config = config.clone();
+                let found = found.clone();
+                let device_mapping = device_mapping.clone();
+                move |ip: Ipv4Addr, mac: MacAddr| {
+                    let device = new_device(ip.to_string(), mac.to_string(), &vendors);
+                    // Excluded hosts missing from the ARP table are only recognized by their reply
+                    if !config.keeps_device(&device, false) {
+                        return None;
+                    }
+                    send_device(&found, &device, device_mapping.as_ref());
+                    Some(device)
+                }
+            };
+            let arp_future = perform_optimized_arp_scan(
+                &pnet_iface,
+                source_ip,
+                source_mac,
+                target_ips,
+                config,
+                control,
+                record_device,
+            );
 
-            // Also read the existing ARP table for devices that might not respond to active scanning
-            let arp_table_future = read_arp_table();
+            let mdns_future = discover_mdns_devices(config.mdns_timeout, &config.mdns);
 
-            let (arp_devices, mdns_devices, arp_table_devices) =
-                tokio::join!(arp_future, mdns_future, arp_table_future);
+            let (arp_devices, mdns_devices) = tokio::join!(arp_future, mdns_future);
+            let arp_devices = arp_devices?;
 
             devices.extend(arp_devices);
 
             // Merge ARP table devices (don't overwrite active scan results)
+            let mut passive_ids = HashSet::new();
             for (ip, arp_device) in arp_table_devices {
                 // Check if we already have a device with this IP address
                 let ip_exists = devices.values().any(|device| device.ip_address == ip);
-                if !ip_exists {
+                if !ip_exists && config.keeps_device(&arp_device, true) {
                     // println!("🔍 Debug: Adding device from ARP table: {}", ip);
+                    send_device(&found, &arp_device, device_mapping.as_ref());
+                    passive_ids.insert(arp_device.id.clone());
                     devices.insert(arp_device.id.clone(), arp_device);
                 }
             }
 
+            // Addresses advertised in mDNS AAAA records are solicited directly; everything
+            // else on the IPv6 side has to answer the all-nodes ping.
+            let ndp_targets: Vec<Ipv6Addr> = mdns_devices
+                .keys()
+                .filter(|ip| !config.is_ip_excluded(ip))
+                .filter_map(|ip| match ip {
+                    IpAddr::V6(ipv6) => Some(*ipv6),
+                    _ => None,
+                })
+                .collect();
+            let ndp_neighbors =
+                perform_ndp_scan(&pnet_iface, source_mac, ndp_targets, config.ndp_timeout).await;
+
+            let ndp_devices =
+                merge_ndp_neighbors(&mut devices, ndp_neighbors, config, &vendors, &passive_ids);
+            for device in &ndp_devices {
+                send_device(&found, device, device_mapping.as_ref());
+            }
+
+            // Streamed devices have all been sent; probing them would produce results
+            // nobody receives.
+            if found.is_some() {
+                return Some(devices.into_values().collect());
+            }
+
             for (ip, (mdns_name, service_types)) in mdns_devices {
-                if let IpAddr::V4(ipv4) = ip {
-                    let ip_string = ipv4.to_string();
-
-                    // Find the device with matching IP address
-                    for device in devices.values_mut() {
-                        if device.ip_address == ip_string {
-                            if device.hostname.is_none() {
-                                device.hostname = Some(mdns_name.clone());
-                            }
-                            if device.device_name.is_none() && !mdns_name.is_empty() {
-                                device.device_name = Some(mdns_name.clone());
-                            }
-                            device.mdns_service_types = Some(service_types.clone());
-                            break; // Found the device, no need to continue
+                let ip_string = ip.to_string();
+
+                // Find the device with matching IP address
+                for device in devices.values_mut() {
+                    if device.ip_address == ip_string
+                        || device.ipv6_address.as_deref() == Some(ip_string.as_str())
+                    {
+                        if !mdns_name.is_empty() {
+                            device.signals.mdns_name = Some(mdns_name.clone());
                         }
+                        device.mdns_service_types = Some(service_types.clone());
+                        break; // Found the device, no need to continue
                     }
                 }
             }
 
+            // Excluded hosts kept from the ARP table are never probed.
             let all_device_info: Vec<(IpAddr, String, bool)> = devices
                 .iter()
+                .filter(|(_device_id, device)| !config.is_device_excluded(device))
                 .filter_map(|(_device_id, device)| {
                     if let Ok(ip) = device.ip_address.parse::<IpAddr>() {
-                        let vendor = device.vendor.clone().unwrap_or_default();
-                        let needs_dns = device.hostname.is_none();
+                        let vendor = device.signals.oui_vendor.clone().unwrap_or_default();
+                        let needs_dns = device.signals.mdns_name.is_none();
                         Some((ip, vendor, needs_dns))
                     } else {
                         None
@@ -180,9 +321,13 @@ pub async fn scan_local_network_devices() -> Vec<LocalNetworkDevice> {
                 .collect();
 
             if !all_device_info.is_empty() {
+                let port_limit = Arc::new(Semaphore::new(config.max_port_connections.max(1)));
+                let port_timeout = config.port_timeout;
+
                 let discovery_tasks: Vec<_> = all_device_info
                     .into_iter()
                     .map(|(ip, vendor, needs_dns)| {
+                        let port_limit = port_limit.clone();
                         tokio::spawn(async move {
                             let hostname = if needs_dns {
                                 perform_reverse_dns_lookup(ip).await
@@ -190,27 +335,39 @@ pub async fn scan_local_network_devices() -> Vec<LocalNetworkDevice> {
                                 None
                             };
 
-                            let device_name = discover_smart_device_name(ip, &vendor).await;
+                            // HTTP probes would need a scope id for link-local IPv6, so only
+                            // IPv4 hosts are probed; reverse DNS works for both families.
+                            let (device_name, open_ports) = if ip.is_ipv4() {
+                                let (device_name, open_ports) = tokio::join!(
+                                    discover_smart_device_name(ip, &vendor),
+                                    scan_ports_limited(
+                                        ip,
+                                        DISCOVERY_PORTS,
+                                        port_timeout,
+                                        port_limit,
+                                    )
+                                );
+                                (device_name, Some(open_ports))
+                            } else {
+                                (None, None)
+                            };
 
-                            (ip, hostname, device_name)
+                            (ip, hostname, device_name, open_ports)
                         })
                     })
                     .collect();
 
                 let discovery_results = join_all(discovery_tasks).await;
                 for task_result in discovery_results {
-                    if let Ok((ip, hostname, device_name)) = task_result {
+                    if let Ok((ip, hostname, device_name, open_ports)) = task_result {
                         let ip_string = ip.to_string();
 
                         // Find the device with matching IP address
                         for (_device_id, device) in devices.iter_mut() {
                             if device.ip_address == ip_string {
-                                if device.hostname.is_none() {
-                                    device.hostname = hostname.clone();
-                                }
-                                if device.device_name.is_none() {
-                                    device.device_name = device_name.clone();
-                                }
+                                device.signals.reverse_dns_hostname = hostname.clone();
+                                device.signals.smart_device_name = device_name.clone();
+                                device.open_ports = open_ports.clone();
                                 break; // Found the device, no need to continue
                             }
                         }
@@ -218,37 +375,138 @@ pub async fn scan_local_network_devices() -> Vec<LocalNetworkDevice> {
                 }
             }
         }
-        None => {}
+        None => return None,
     }
 
-    if let Some(ref mapping) = device_mapping {
-        for device in devices.values_mut() {
-            if device.device_name.is_none() {
-                if let Some(mapped_name) = mapping.get_device_name(&device.mac_address) {
-                    device.device_name = Some(mapped_name);
-                }
-            }
-        }
+    for device in devices.values_mut() {
+        identify_device(device, device_mapping.as_ref());
     }
 
     let _elapsed = start_time.elapsed();
 
-    devices.into_values().collect()
+    Some(devices.into_values().collect())
+}
+
+fn new_device(
+    ip_address: String,
+    mac_address: String,
+    vendors: &VendorMapping,
+) -> LocalNetworkDevice {
+    LocalNetworkDevice {
+        id: Uuid::new_v4().to_string(),
+        signals: DeviceSignals {
+            oui_vendor: vendors.get_vendor(&mac_address),
+            ..Default::default()
+        },
+        ip_address,
+        mac_address,
+        ..Default::default()
+    }
+}
+
+/// Applies the user's device mapping and resolves the collected signals into the device's
+/// name, hostname, vendor and class.
+fn identify_device(device: &mut LocalNetworkDevice, device_mapping: Option<&DeviceMapping>) {
+    if let Some(mapping) = device_mapping
+        && let Some(config) = mapping.get_device(&device.mac_address)
+    {
+        device.signals.mapped_name = mapping.get_device_name(&device.mac_address);
+        device.signals.mapped_device_type = Some(config.device_type.clone());
+    }
+
+    let fingerprint = fingerprint(device);
+    device.device_name = fingerprint.name;
+    device.hostname = fingerprint.hostname;
+    device.vendor = fingerprint.vendor;
+    device.device_class = fingerprint.device_class;
+}
+
+/// Streams an identified copy of `device` when the scan has a `found` channel.
+fn send_device(
+    found: &Option<UnboundedSender<LocalNetworkDevice>>,
+    device: &LocalNetworkDevice,
+    device_mapping: Option<&DeviceMapping>,
+) {
+    if let Some(found) = found {
+        let mut device = device.clone();
+        identify_device(&mut device, device_mapping);
+        let _ = found.unbounded_send(device);
+    }
 }
 
+/// Records NDP `neighbors` in `devices` and returns the IPv6-only devices it added.
+/// Dual-stack hosts are matched by MAC to the entry ARP already found and take on the IPv6
+/// address, unless it would replace a global address with a link-local one. The merged
+/// devices then go through `keeps_device`, so an excluded IPv6 address also drops the
+/// host's IPv4 entry; `passive_ids` are the entries read from the system ARP table.
+fn merge_ndp_neighbors(
+    devices: &mut HashMap<String, LocalNetworkDevice>,
+    neighbors: impl IntoIterator<Item = (Ipv6Addr, MacAddr)>,
+    config: &ScanConfig,
+    vendors: &VendorMapping,
+    passive_ids: &HashSet<String>,
+) -> Vec<LocalNetworkDevice> {
+    let mut added_ids = Vec::new();
+
+    for (ipv6, mac) in neighbors {
+        let mac = mac.to_string();
+
+        if let Some(device) = devices
+            .values_mut()
+            .find(|device| device.mac_address.eq_ignore_ascii_case(&mac))
+        {
+            let keep_existing = device
+                .ipv6_address
+                .as_ref()
+                .and_then(|existing| existing.parse::<Ipv6Addr>().ok())
+                .is_some_and(|existing| {
+                    !existing.is_unicast_link_local() || config.is_ip_excluded(&existing.into())
+                });
+            // An excluded address is always recorded so the exclusion applies to the host
+            if !keep_existing || config.is_ip_excluded(&ipv6.into()) {
+                device.ipv6_address = Some(ipv6.to_string());
+            }
+            continue;
+        }
+
+        let mut device = new_device(ipv6.to_string(), mac, vendors);
+        device.ipv6_address = Some(ipv6.to_string());
+        added_ids.push(device.id.clone());
+        devices.insert(device.id.clone(), device);
+    }
+
+    devices.retain(|id, device| config.keeps_device(device, passive_ids.contains(id)));
+    added_ids
+        .iter()
+        .filter_map(|id| devices.get(id).cloned())
+        .collect()
+}
+
+/// `record_device` is called once for the first reply from each host and returns the device
+/// to keep, if any. Returns `None` when no datalink channel could be opened.
 async fn perform_optimized_arp_scan(
     pnet_iface: &pnet::datalink::NetworkInterface,
     source_ip: Ipv4Addr,
     source_mac: MacAddr,
     target_ips: Vec<Ipv4Addr>,
-) -> HashMap<String, LocalNetworkDevice> {
-    let devices = Arc::new(Mutex::new(HashMap::new()));
-
-    let (mut tx, mut rx) = match datalink::channel(pnet_iface, Default::default()) {
+    config: &ScanConfig,
+    control: ScanControl,
+    record_device: impl Fn(Ipv4Addr, MacAddr) -> Option<LocalNetworkDevice> + Send + 'static,
+) -> Option<HashMap<String, LocalNetworkDevice>> {
+    let devices: Arc<Mutex<HashMap<String, LocalNetworkDevice>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+
+    // Without a read timeout `rx.next()` blocks until a packet arrives, so on a quiet
+    // network neither the timeout nor cancellation would be noticed.
+    let channel_config = datalink::Config {
+        read_timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    let (mut tx, mut rx) = match datalink::channel(pnet_iface, channel_config) {
         Ok(Channel::Ethernet(tx, rx)) => (tx, rx),
         Ok(_) => {
             log_error(LogType::NetworkScanner, "Unsupported channel type", None).await;
-            return HashMap::new();
+            return None;
         }
         Err(e) => {
             log_error(
@@ -257,15 +515,23 @@ async fn perform_optimized_arp_scan(
                 Some(&e.to_string()),
             )
             .await;
-            return HashMap::new();
+            return None;
         }
     };
 
     let send_task = {
         let target_ips = target_ips.clone();
+        let control = control.clone();
+        let batch_size = config.batch_size.max(1);
         tokio::spawn(async move {
-            const BATCH_SIZE: usize = 10;
-            for batch in target_ips.chunks(BATCH_SIZE) {
+            for batch in target_ips.chunks(batch_size) {
+                if control.is_cancelled() {
+                    break;
+                }
+                while control.is_paused() && !control.is_cancelled() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+
                 let batch_tasks: Vec<_> = batch
                     .iter()
                     .map(|&target_ip_v4| {
@@ -313,103 +579,398 @@ async fn perform_optimized_arp_scan(
 
     let receive_task = {
         let devices = devices.clone();
-        tokio::spawn(async move {
-            let start_time = Instant::now();
-            let timeout_duration = Duration::from_millis(600);
-
-            while start_time.elapsed() < timeout_duration {
-                match rx.next() {
-                    Ok(packet) => {
-                        let Some(ethernet) = EthernetPacket::new(packet) else {
-                            continue;
-                        };
-                        if ethernet.get_ethertype() == EtherTypes::Arp {
-                            let Some(arp) = ArpPacket::new(ethernet.payload()) else {
-                                continue;
-                            };
-                            if arp.get_operation() == ArpOperations::Reply {
-                                let source_ip = arp.get_sender_proto_addr();
-                                let source_mac = arp.get_sender_hw_addr();
-
-                                // Debug: Check if this is one of the missing devices
-                                if source_ip.to_string() == "192.168.1.31"
-                                    || source_ip.to_string() == "192.168.1.32"
-                                {
-                                    // println!(
-                                    //     "🔍 Debug: Found missing device {} with MAC {}",
-                                    //     source_ip, source_mac
-                                    // );
-                                }
-
-                                let device = LocalNetworkDevice {
-                                    id: Uuid::new_v4().to_string(),
-                                    ip_address: source_ip.to_string(),
-                                    mac_address: source_mac.to_string(),
-                                    hostname: None,
-                                    device_name: None,
-                                    vendor: get_vendor_from_mac(&source_mac.to_string()),
-                                    mdns_service_types: None,
-                                };
-
-                                let mut devices = devices.lock().unwrap();
-                                devices.insert(device.id.clone(), device);
-                            }
-                        }
+        let timeout_duration = config.arp_timeout;
+        // `rx.next()` blocks for up to the read timeout, so it must not run on a runtime worker.
+        tokio::task::spawn_blocking(move || {
+            let frames = std::iter::from_fn(|| Some(rx.next().ok().map(|frame| frame.to_vec())));
+
+            receive_arp_replies(
+                frames,
+                &control,
+                timeout_duration,
+                |source_ip, source_mac| {
+                    let ip_string = source_ip.to_string();
+                    let mut devices = devices.lock().unwrap();
+                    // Hosts often answer more than once; keep the first reply
+                    if devices
+                        .values()
+                        .any(|device| device.ip_address == ip_string)
+                    {
+                        return;
                     }
-                    Err(_) => {
-                        tokio::time::sleep(Duration::from_millis(10)).await;
+
+                    if let Some(device) = record_device(source_ip, source_mac) {
+                        devices.insert(device.id.clone(), device);
                     }
-                }
-            }
+                },
+            );
         })
     };
 
     let _ = tokio::join!(send_task, receive_task);
 
     match devices.lock() {
-        Ok(devices_map) => devices_map.clone(),
+        Ok(devices_map) => Some(devices_map.clone()),
+        Err(_) => Some(HashMap::new()),
+    }
+}
+
+/// Hosts in `scan_range` to send ARP requests to. Our own address and excluded IPs are
+/// skipped, as are hosts the system ARP table maps to an excluded MAC prefix.
+fn sweep_targets(
+    scan_range: &IpNet,
+    source_ip: Ipv4Addr,
+    config: &ScanConfig,
+    arp_table: &HashMap<String, LocalNetworkDevice>,
+) -> Vec<Ipv4Addr> {
+    let excluded_by_mac: HashSet<IpAddr> = arp_table
+        .values()
+        .filter(|device| config.is_mac_excluded(&device.mac_address))
+        .filter_map(|device| device.ip_address.parse().ok())
+        .collect();
+
+    scan_range
+        .hosts()
+        .filter(|ip| !config.is_ip_excluded(ip) && !excluded_by_mac.contains(ip))
+        .filter_map(|ip| match ip {
+            IpAddr::V4(ipv4) if ipv4 != source_ip => Some(ipv4),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Reads frames until `timeout_duration` of unpaused listening has passed or the scan is
+/// cancelled, calling `on_reply` for each ARP reply. `frames` yields `None` when nothing
+/// arrived within the read timeout. No frames are pulled while the scan is paused, and paused
+/// time does not count toward the timeout.
+fn receive_arp_replies(
+    mut frames: impl Iterator<Item = Option<Vec<u8>>>,
+    control: &ScanControl,
+    timeout_duration: Duration,
+    mut on_reply: impl FnMut(Ipv4Addr, MacAddr),
+) {
+    let mut active_time = Duration::ZERO;
+    let mut last_tick = Instant::now();
+
+    while active_time < timeout_duration && !control.is_cancelled() {
+        let now = Instant::now();
+        if control.is_paused() {
+            last_tick = now;
+            std::thread::sleep(Duration::from_millis(10));
+            continue;
+        }
+        active_time += now - last_tick;
+        last_tick = now;
+
+        let Some(frame) = frames.next() else {
+            break;
+        };
+        if let Some((source_ip, source_mac)) = frame.as_deref().and_then(parse_arp_reply) {
+            on_reply(source_ip, source_mac);
+        }
+    }
+}
+
+fn parse_arp_reply(frame: &[u8]) -> Option<(Ipv4Addr, MacAddr)> {
+    let ethernet = EthernetPacket::new(frame)?;
+    if ethernet.get_ethertype() != EtherTypes::Arp {
+        return None;
+    }
+    let arp = ArpPacket::new(ethernet.payload())?;
+    if arp.get_operation() != ArpOperations::Reply {
+        return None;
+    }
+    Some((arp.get_sender_proto_addr(), arp.get_sender_hw_addr()))
+}
+
+async fn read_arp_table(
+    subnet: IpNet,
+    vendors: &VendorMapping,
+) -> HashMap<String, LocalNetworkDevice> {
+    // Read the system ARP table
+    match Command::new("arp").arg("-a").output() {
+        Ok(output) => parse_arp_table(&String::from_utf8_lossy(&output.stdout), &subnet, vendors),
         Err(_) => HashMap::new(),
     }
 }
 
-async fn read_arp_table() -> HashMap<String, LocalNetworkDevice> {
+/// Parses `arp -a` output, keeping only entries inside `subnet`.
+pub fn parse_arp_table(
+    arp_output: &str,
+    subnet: &IpNet,
+    vendors: &VendorMapping,
+) -> HashMap<String, LocalNetworkDevice> {
     let mut devices = HashMap::new();
 
-    // Read the system ARP table
-    if let Ok(output) = Command::new("arp").arg("-a").output() {
-        if let Ok(arp_output) = String::from_utf8(output.stdout) {
-            // println!("🔍 Debug: Reading system ARP table...");
-
-            for line in arp_output.lines() {
-                // Parse lines like: ? (192.168.1.31) at cc:40:85:d1:4e:94 on en0 ifscope [ethernet]
-                if let Some(captures) =
-                    regex::Regex::new(r"\((\d+\.\d+\.\d+\.\d+)\) at ([a-fA-F0-9:]{17})")
-                        .ok()
-                        .and_then(|re| re.captures(line))
-                {
-                    let ip = captures.get(1).unwrap().as_str();
-                    let mac = captures.get(2).unwrap().as_str();
-
-                    // Only include devices from our subnet
-                    if ip.starts_with("192.168.1.") {
-                        // println!("🔍 Debug: Found in ARP table: {} -> {}", ip, mac);
-
-                        let device = LocalNetworkDevice {
-                            id: Uuid::new_v4().to_string(),
-                            ip_address: ip.to_string(),
-                            mac_address: mac.to_string(),
-                            hostname: None,
-                            device_name: None,
-                            vendor: get_vendor_from_mac(mac),
-                            mdns_service_types: None,
-                        };
-
-                        devices.insert(ip.to_string(), device);
-                    }
-                }
-            }
+    // Parse lines like: ? (192.168.1.31) at cc:40:85:d1:4e:94 on en0 ifscope [ethernet]
+    let Ok(entry_pattern) = regex::Regex::new(r"\((\d+\.\d+\.\d+\.\d+)\) at ([a-fA-F0-9:]{17})")
+    else {
+        return devices;
+    };
+
+    for captures in arp_output
+        .lines()
+        .filter_map(|line| entry_pattern.captures(line))
+    {
+        let ip = &captures[1];
+        let mac = &captures[2];
+
+        let Ok(ip_addr) = ip.parse::<IpAddr>() else {
+            continue;
+        };
+        if !subnet.contains(&ip_addr) {
+            continue;
         }
+
+        // println!("🔍 Debug: Found in ARP table: {} -> {}", ip, mac);
+        let device = new_device(ip.to_string(), mac.to_string(), vendors);
+        devices.insert(ip.to_string(), device);
     }
 
     devices
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arp_reply(source_ip: Ipv4Addr, source_mac: MacAddr) -> Vec<u8> {
+        let mut buffer = vec![0u8; 42];
+        let mut ethernet = MutableEthernetPacket::new(&mut buffer).unwrap();
+        ethernet.set_destination(MacAddr::broadcast());
+        ethernet.set_source(source_mac);
+        ethernet.set_ethertype(EtherTypes::Arp);
+
+        let mut arp = MutableArpPacket::new(ethernet.payload_mut()).unwrap();
+        arp.set_hardware_type(ArpHardwareTypes::Ethernet);
+        arp.set_protocol_type(EtherTypes::Ipv4);
+        arp.set_hw_addr_len(6);
+        arp.set_proto_addr_len(4);
+        arp.set_operation(ArpOperations::Reply);
+        arp.set_sender_hw_addr(source_mac);
+        arp.set_sender_proto_addr(source_ip);
+        buffer
+    }
+
+    #[test]
+    fn ndp_neighbors_join_the_arp_entry_with_the_same_mac() {
+        let config = ScanConfig::default();
+        let vendors = VendorMapping::default();
+        let dual_stack = new_device("192.168.1.40".into(), "1e:c0:3e:12:34:56".into(), &vendors);
+        let dual_stack_id = dual_stack.id.clone();
+        let mut devices = HashMap::from([(dual_stack_id.clone(), dual_stack)]);
+        let global: Ipv6Addr = "2001:db8::40".parse().unwrap();
+        let link_local: Ipv6Addr = "fe80::40".parse().unwrap();
+        let ipv6_only: Ipv6Addr = "2001:db8::41".parse().unwrap();
+        let neighbors = [
+            (global, MacAddr::new(0x1e, 0xc0, 0x3e, 0x12, 0x34, 0x56)),
+            (link_local, MacAddr::new(0x1e, 0xc0, 0x3e, 0x12, 0x34, 0x56)),
+            (ipv6_only, MacAddr::new(0xcc, 0x40, 0x85, 0xd1, 0x4e, 0x94)),
+        ];
+
+        let added =
+            merge_ndp_neighbors(&mut devices, neighbors, &config, &vendors, &HashSet::new());
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(
+            devices[&dual_stack_id].ipv6_address,
+            Some(global.to_string())
+        );
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].ip_address, ipv6_only.to_string());
+        assert_eq!(added[0].ipv6_address, Some(ipv6_only.to_string()));
+    }
+
+    #[test]
+    fn excluded_ipv6_address_drops_the_dual_stack_host() {
+        let excluded: Ipv6Addr = "2001:db8::40".parse().unwrap();
+        let config = ScanConfig {
+            exclude_ips: vec![IpAddr::V6(excluded)],
+            ..Default::default()
+        };
+        let vendors = VendorMapping::default();
+        let swept = new_device("192.168.1.40".into(), "1e:c0:3e:12:34:56".into(), &vendors);
+        let cached = new_device("192.168.1.41".into(), "cc:40:85:d1:4e:94".into(), &vendors);
+        let cached_id = cached.id.clone();
+        let mut devices = HashMap::from([(swept.id.clone(), swept), (cached_id.clone(), cached)]);
+        let neighbors = [
+            (excluded, MacAddr::new(0x1e, 0xc0, 0x3e, 0x12, 0x34, 0x56)),
+            (excluded, MacAddr::new(0xcc, 0x40, 0x85, 0xd1, 0x4e, 0x94)),
+        ];
+
+        let passive_ids = HashSet::from([cached_id.clone()]);
+        merge_ndp_neighbors(&mut devices, neighbors, &config, &vendors, &passive_ids);
+        assert!(devices.is_empty());
+
+        // A host read from the ARP table is kept when asked to, but marked excluded so it
+        // is never probed.
+        let config = ScanConfig {
+            include_excluded_passive: true,
+            ..config
+        };
+        let cached = new_device("192.168.1.41".into(), "cc:40:85:d1:4e:94".into(), &vendors);
+        let mut devices = HashMap::from([(cached_id.clone(), cached)]);
+        let neighbors = [(excluded, MacAddr::new(0xcc, 0x40, 0x85, 0xd1, 0x4e, 0x94))];
+        merge_ndp_neighbors(&mut devices, neighbors, &config, &vendors, &passive_ids);
+        assert!(config.is_device_excluded(&devices[&cached_id]));
+    }
+
+    #[test]
+    fn paused_scan_pulls_no_frames_until_resumed() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let reply = arp_reply(
+            Ipv4Addr::new(192, 168, 1, 20),
+            MacAddr::new(1, 2, 3, 4, 5, 6),
+        );
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let control = ScanControl::default();
+        control.pause();
+
+        let receiver = {
+            let pulled = pulled.clone();
+            let control = control.clone();
+            std::thread::spawn(move || {
+                let frames = std::iter::from_fn(|| {
+                    pulled.fetch_add(1, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(1));
+                    Some(Some(reply.clone()))
+                });
+                let mut replies = Vec::new();
+                let start_time = Instant::now();
+                receive_arp_replies(frames, &control, Duration::from_millis(100), |ip, mac| {
+                    replies.push((ip, mac));
+                });
+                (replies, start_time.elapsed())
+            })
+        };
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(pulled.load(Ordering::SeqCst), 0);
+
+        control.resume();
+        let (replies, elapsed) = receiver.join().unwrap();
+
+        assert!(pulled.load(Ordering::SeqCst) > 0);
+        assert_eq!(
+            replies[0],
+            (
+                Ipv4Addr::new(192, 168, 1, 20),
+                MacAddr::new(1, 2, 3, 4, 5, 6)
+            )
+        );
+        // Most of the 150ms spent paused did not count toward the 100ms timeout.
+        assert!(elapsed >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn sweep_skips_excluded_ips_and_cached_excluded_macs() {
+        let config = ScanConfig {
+            exclude_ips: vec!["192.168.1.2".parse().unwrap()],
+            exclude_mac_prefixes: vec!["cc:40:85".to_string()],
+            ..Default::default()
+        };
+        let arp_table = HashMap::from([
+            (
+                "192.168.1.3".to_string(),
+                LocalNetworkDevice {
+                    ip_address: "192.168.1.3".to_string(),
+                    mac_address: "cc:40:85:d1:4e:94".to_string(),
+                    ..Default::default()
+                },
+            ),
+            (
+                "192.168.1.4".to_string(),
+                LocalNetworkDevice {
+                    ip_address: "192.168.1.4".to_string(),
+                    mac_address: "d8:be:65:00:00:01".to_string(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let scan_range: IpNet = "192.168.1.0/29".parse().unwrap();
+        let source_ip = Ipv4Addr::new(192, 168, 1, 1);
+
+        let targets = sweep_targets(&scan_range, source_ip, &config, &arp_table);
+
+        let expected: Vec<Ipv4Addr> = [4, 5, 6]
+            .map(|host| Ipv4Addr::new(192, 168, 1, host))
+            .into();
+        assert_eq!(targets, expected);
+    }
+
+    #[test]
+    fn streamed_devices_are_identified_before_they_are_sent() {
+        let (found_tx, mut found_rx) = mpsc::unbounded();
+        let device = LocalNetworkDevice {
+            mac_address: "cc:40:85:d1:4e:94".to_string(),
+            signals: DeviceSignals {
+                oui_vendor: Some("Xiaomi".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        send_device(&Some(found_tx), &device, None);
+
+        let sent = found_rx.try_next().unwrap().unwrap();
+        assert_eq!(sent.vendor.as_deref(), Some("Xiaomi"));
+    }
+
+    #[tokio::test]
+    async fn cancelling_the_stream_after_the_first_device_ends_it() {
+        use futures::StreamExt;
+
+        let cancellation = CancellationToken::new();
+        let stream = spawn_device_stream(cancellation.clone(), |found| async move {
+            let _ = found.unbounded_send(LocalNetworkDevice {
+                ip_address: "192.168.1.20".to_string(),
+                ..Default::default()
+            });
+            // A scan that would otherwise never finish.
+            std::future::pending::<()>().await;
+        });
+        futures::pin_mut!(stream);
+
+        let first = stream.next().await.unwrap();
+        assert_eq!(first.ip_address, "192.168.1.20");
+
+        cancellation.cancel();
+        let next = tokio::time::timeout(Duration::from_secs(1), stream.next()).await;
+        assert!(matches!(next, Ok(None)));
+    }
+
+    #[test]
+    fn arp_table_entries_are_kept_only_inside_the_subnet() {
+        let arp_output = "\
+? (10.0.0.12) at cc:40:85:d1:4e:94 on en0 ifscope [ethernet]
+? (10.0.1.7) at d8:be:65:00:00:01 on en0 ifscope [ethernet]
+? (10.0.0.13) at (incomplete) on en0 ifscope [ethernet]";
+        let subnet: IpNet = "10.0.0.0/24".parse().unwrap();
+
+        let devices = parse_arp_table(arp_output, &subnet, &VendorMapping::default());
+
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices["10.0.0.12"].mac_address, "cc:40:85:d1:4e:94");
+    }
+
+    #[test]
+    fn blocking_wrapper_has_the_expected_signature() {
+        let _: fn(&ScanConfig) -> std::io::Result<Vec<LocalNetworkDevice>> =
+            scan_local_network_devices_blocking;
+    }
+
+    #[test]
+    fn cancelled_scan_stops_receiving() {
+        let control = ScanControl::default();
+        control.cancel();
+
+        let mut replies = 0;
+        let frames = std::iter::repeat_with(|| None);
+        receive_arp_replies(frames, &control, Duration::from_secs(60), |_, _| {
+            replies += 1
+        });
+
+        assert_eq!(replies, 0);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::core::logger::{log_error, LogType};
+use pnet::datalink::{self, Channel, NetworkInterface};
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
+use pnet::packet::icmpv6::echo_request::MutableEchoRequestPacket;
+use pnet::packet::icmpv6::ndp::{
+    MutableNeighborSolicitPacket, NdpOption, NdpOptionTypes, NeighborAdvertPacket,
+};
+use pnet::packet::icmpv6::{self, Icmpv6Code, Icmpv6Packet, Icmpv6Types, MutableIcmpv6Packet};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv6::{Ipv6Packet, MutableIpv6Packet};
+use pnet::packet::{MutablePacket, Packet};
+use pnet::util::MacAddr;
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+use std::time::{Duration, Instant};
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPV6_HEADER_LEN: usize = 40;
+// 24-byte message followed by an 8-byte source link-layer address option.
+const NEIGHBOR_SOLICIT_LEN: usize = 32;
+const ECHO_REQUEST_LEN: usize = 8;
+const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// IPv6 counterpart of the ARP sweep. A /64 is far too large to solicit host by host, so
+/// neighbors are found by pinging the all-nodes multicast group, plus a Neighbor
+/// Solicitation for each address already known from other sources (e.g. mDNS AAAA records).
+pub async fn perform_ndp_scan(
+    pnet_iface: &NetworkInterface,
+    source_mac: MacAddr,
+    targets: Vec<Ipv6Addr>,
+    timeout_duration: Duration,
+) -> HashMap<Ipv6Addr, MacAddr> {
+    let mut neighbors = HashMap::new();
+
+    let Some(source_ip) = select_source_address(pnet_iface) else {
+        return neighbors;
+    };
+
+    let config = datalink::Config {
+        read_timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    let (mut tx, mut rx) = match datalink::channel(pnet_iface, config) {
+        Ok(Channel::Ethernet(tx, rx)) => (tx, rx),
+        Ok(_) => {
+            log_error(
+                LogType::NetworkScanner,
+                "Unsupported channel type for NDP",
+                None,
+            )
+            .await;
+            return neighbors;
+        }
+        Err(e) => {
+            log_error(
+                LogType::NetworkScanner,
+                "Failed to create datalink channel - NDP scanning requires elevated privileges",
+                Some(&e.to_string()),
+            )
+            .await;
+            return neighbors;
+        }
+    };
+
+    let probes = build_all_nodes_echo_request(source_mac, source_ip)
+        .into_iter()
+        .chain(
+            targets
+                .iter()
+                .filter_map(|&target| build_neighbor_solicitation(source_mac, source_ip, target)),
+        );
+    for probe in probes {
+        let _ = tx.send_to(&probe, None);
+    }
+
+    let receive_task = tokio::task::spawn_blocking(move || {
+        let start_time = Instant::now();
+        let mut neighbors = HashMap::new();
+
+        while start_time.elapsed() < timeout_duration {
+            let Ok(frame) = rx.next() else {
+                continue;
+            };
+            if let Some((ip, mac)) = parse_neighbor_reply(frame)
+                && mac != source_mac
+                && !ip.is_unspecified()
+            {
+                neighbors.insert(ip, mac);
+            }
+        }
+
+        neighbors
+    });
+
+    if let Ok(received) = receive_task.await {
+        neighbors.extend(received);
+    }
+    neighbors
+}
+
+/// Link-local is preferred: it is always present and is what neighbors answer on.
+fn select_source_address(pnet_iface: &NetworkInterface) -> Option<Ipv6Addr> {
+    let addresses: Vec<Ipv6Addr> = pnet_iface
+        .ips
+        .iter()
+        .filter_map(|ip_network| match ip_network.ip() {
+            std::net::IpAddr::V6(ipv6) => Some(ipv6),
+            _ => None,
+        })
+        .collect();
+
+    addresses
+        .iter()
+        .find(|ip| ip.is_unicast_link_local())
+        .or_else(|| addresses.first())
+        .copied()
+}
+
+pub fn solicited_node_multicast(target: Ipv6Addr) -> Ipv6Addr {
+    let octets = target.octets();
+    Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | octets[13] as u16,
+        u16::from_be_bytes([octets[14], octets[15]]),
+    )
+}
+
+pub fn multicast_mac(group: Ipv6Addr) -> MacAddr {
+    let octets = group.octets();
+    MacAddr::new(0x33, 0x33, octets[12], octets[13], octets[14], octets[15])
+}
+
+pub fn build_neighbor_solicitation(
+    source_mac: MacAddr,
+    source_ip: Ipv6Addr,
+    target_ip: Ipv6Addr,
+) -> Option<Vec<u8>> {
+    let destination = solicited_node_multicast(target_ip);
+
+    let mut message = vec![0u8; NEIGHBOR_SOLICIT_LEN];
+    {
+        let mut solicit = MutableNeighborSolicitPacket::new(&mut message)?;
+        solicit.set_icmpv6_type(Icmpv6Types::NeighborSolicit);
+        solicit.set_icmpv6_code(Icmpv6Code(0));
+        solicit.set_target_addr(target_ip);
+        solicit.set_options(&[NdpOption {
+            option_type: NdpOptionTypes::SourceLLAddr,
+            length: 1,
+            data: source_mac.octets().to_vec(),
+        }]);
+    }
+    set_icmpv6_checksum(&mut message, source_ip, destination)?;
+
+    build_ipv6_frame(source_mac, source_ip, destination, &message)
+}
+
+pub fn build_all_nodes_echo_request(source_mac: MacAddr, source_ip: Ipv6Addr) -> Option<Vec<u8>> {
+    let mut message = vec![0u8; ECHO_REQUEST_LEN];
+    {
+        let mut echo = MutableEchoRequestPacket::new(&mut message)?;
+        echo.set_icmpv6_type(Icmpv6Types::EchoRequest);
+        echo.set_icmpv6_code(Icmpv6Code(0));
+        echo.set_identifier(std::process::id() as u16);
+        echo.set_sequence_number(1);
+    }
+    set_icmpv6_checksum(&mut message, source_ip, ALL_NODES)?;
+
+    build_ipv6_frame(source_mac, source_ip, ALL_NODES, &message)
+}
+
+/// Returns the responder's address and MAC for a Neighbor Advertisement or Echo Reply.
+pub fn parse_neighbor_reply(frame: &[u8]) -> Option<(Ipv6Addr, MacAddr)> {
+    let ethernet = EthernetPacket::new(frame)?;
+    if ethernet.get_ethertype() != EtherTypes::Ipv6 {
+        return None;
+    }
+    let ipv6 = Ipv6Packet::new(ethernet.payload())?;
+    if ipv6.get_next_header() != IpNextHeaderProtocols::Icmpv6 {
+        return None;
+    }
+    let icmp = Icmpv6Packet::new(ipv6.payload())?;
+
+    match icmp.get_icmpv6_type() {
+        Icmpv6Types::NeighborAdvert => {
+            let advert = NeighborAdvertPacket::new(ipv6.payload())?;
+            let mac = advert
+                .get_options_iter()
+                .find(|option| option.get_option_type() == NdpOptionTypes::TargetLLAddr)
+                .and_then(|option| mac_from_bytes(option.payload()))
+                .unwrap_or_else(|| ethernet.get_source());
+            Some((advert.get_target_addr(), mac))
+        }
+        Icmpv6Types::EchoReply => Some((ipv6.get_source(), ethernet.get_source())),
+        _ => None,
+    }
+}
+
+fn build_ipv6_frame(
+    source_mac: MacAddr,
+    source_ip: Ipv6Addr,
+    destination_ip: Ipv6Addr,
+    message: &[u8],
+) -> Option<Vec<u8>> {
+    let mut buffer = vec![0u8; ETHERNET_HEADER_LEN + IPV6_HEADER_LEN + message.len()];
+    {
+        let mut ethernet = MutableEthernetPacket::new(&mut buffer)?;
+        ethernet.set_destination(multicast_mac(destination_ip));
+        ethernet.set_source(source_mac);
+        ethernet.set_ethertype(EtherTypes::Ipv6);
+
+        let mut ipv6 = MutableIpv6Packet::new(ethernet.payload_mut())?;
+        ipv6.set_version(6);
+        ipv6.set_payload_length(message.len() as u16);
+        ipv6.set_next_header(IpNextHeaderProtocols::Icmpv6);
+        // RFC 4861 requires 255 so receivers can tell the packet never crossed a router.
+        ipv6.set_hop_limit(255);
+        ipv6.set_source(source_ip);
+        ipv6.set_destination(destination_ip);
+        ipv6.set_payload(message);
+    }
+    Some(buffer)
+}
+
+fn set_icmpv6_checksum(message: &mut [u8], source: Ipv6Addr, destination: Ipv6Addr) -> Option<()> {
+    let checksum = icmpv6::checksum(&Icmpv6Packet::new(message)?, &source, &destination);
+    MutableIcmpv6Packet::new(message)?.set_checksum(checksum);
+    Some(())
+}
+
+fn mac_from_bytes(bytes: &[u8]) -> Option<MacAddr> {
+    match bytes {
+        [a, b, c, d, e, f, ..] => Some(MacAddr::new(*a, *b, *c, *d, *e, *f)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::icmpv6::echo_reply::MutableEchoReplyPacket;
+    use pnet::packet::icmpv6::ndp::{MutableNeighborAdvertPacket, NeighborSolicitPacket};
+
+    const SOURCE_MAC: MacAddr = MacAddr(0x02, 0x00, 0x00, 0x00, 0x00, 0x01);
+    const SOURCE_IP: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const TARGET_IP: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0x1cc0, 0x3eff, 0xfe12, 0x3456);
+    const TARGET_MAC: MacAddr = MacAddr(0x1e, 0xc0, 0x3e, 0x12, 0x34, 0x56);
+
+    fn reply_frame(source_ip: Ipv6Addr, message: &[u8]) -> Vec<u8> {
+        let mut frame = build_ipv6_frame(TARGET_MAC, source_ip, SOURCE_IP, message).unwrap();
+        // Replies are unicast back to us rather than to a multicast group.
+        MutableEthernetPacket::new(&mut frame)
+            .unwrap()
+            .set_destination(SOURCE_MAC);
+        frame
+    }
+
+    #[test]
+    fn solicited_node_group_uses_the_low_24_bits() {
+        let group = solicited_node_multicast(TARGET_IP);
+
+        assert_eq!(group, "ff02::1:ff12:3456".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(
+            multicast_mac(group),
+            MacAddr::new(0x33, 0x33, 0xff, 0x12, 0x34, 0x56)
+        );
+    }
+
+    #[test]
+    fn neighbor_solicitation_is_addressed_to_the_solicited_node_group() {
+        let frame = build_neighbor_solicitation(SOURCE_MAC, SOURCE_IP, TARGET_IP).unwrap();
+
+        let ethernet = EthernetPacket::new(&frame).unwrap();
+        assert_eq!(
+            ethernet.get_destination(),
+            MacAddr::new(0x33, 0x33, 0xff, 0x12, 0x34, 0x56)
+        );
+        assert_eq!(ethernet.get_source(), SOURCE_MAC);
+
+        let ipv6 = Ipv6Packet::new(ethernet.payload()).unwrap();
+        assert_eq!(ipv6.get_hop_limit(), 255);
+        assert_eq!(ipv6.get_destination(), solicited_node_multicast(TARGET_IP));
+
+        let icmp = Icmpv6Packet::new(ipv6.payload()).unwrap();
+        let checksum = icmpv6::checksum(&icmp, &ipv6.get_source(), &ipv6.get_destination());
+        assert_eq!(icmp.get_checksum(), checksum);
+
+        let solicit = NeighborSolicitPacket::new(ipv6.payload()).unwrap();
+        assert_eq!(solicit.get_icmpv6_type(), Icmpv6Types::NeighborSolicit);
+        assert_eq!(solicit.get_target_addr(), TARGET_IP);
+        let source_option = solicit.get_options_iter().next().unwrap();
+        assert_eq!(
+            source_option.get_option_type(),
+            NdpOptionTypes::SourceLLAddr
+        );
+        assert_eq!(mac_from_bytes(source_option.payload()), Some(SOURCE_MAC));
+    }
+
+    #[test]
+    fn neighbor_advertisement_reports_the_target_link_layer_address() {
+        let mut message = vec![0u8; NEIGHBOR_SOLICIT_LEN];
+        {
+            let mut advert = MutableNeighborAdvertPacket::new(&mut message).unwrap();
+            advert.set_icmpv6_type(Icmpv6Types::NeighborAdvert);
+            advert.set_target_addr(TARGET_IP);
+            advert.set_options(&[NdpOption {
+                option_type: NdpOptionTypes::TargetLLAddr,
+                length: 1,
+                data: TARGET_MAC.octets().to_vec(),
+            }]);
+        }
+
+        let frame = reply_frame(TARGET_IP, &message);
+
+        assert_eq!(parse_neighbor_reply(&frame), Some((TARGET_IP, TARGET_MAC)));
+    }
+
+    #[test]
+    fn echo_reply_reports_the_sender() {
+        let mut message = vec![0u8; ECHO_REQUEST_LEN];
+        MutableEchoReplyPacket::new(&mut message)
+            .unwrap()
+            .set_icmpv6_type(Icmpv6Types::EchoReply);
+
+        let frame = reply_frame(TARGET_IP, &message);
+
+        assert_eq!(parse_neighbor_reply(&frame), Some((TARGET_IP, TARGET_MAC)));
+    }
+
+    #[test]
+    fn our_own_solicitation_is_not_a_reply() {
+        let frame = build_neighbor_solicitation(SOURCE_MAC, SOURCE_IP, TARGET_IP).unwrap();
+
+        assert_eq!(parse_neighbor_reply(&frame), None);
+    }
+}
@@ -0,0 +1,66 @@
+use super::types::ScanConfig;
+use futures::future::join_all;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+
+/// TCP connect scan of `ports` on `ip`. Returns the open ports in ascending order.
+/// At most `ScanConfig::default().max_port_connections` connections are open at once.
+#[allow(dead_code)]
+pub async fn scan_ports(ip: IpAddr, ports: &[u16], timeout: Duration) -> Vec<u16> {
+    let limit = Arc::new(Semaphore::new(ScanConfig::default().max_port_connections));
+    scan_ports_limited(ip, ports, timeout, limit).await
+}
+
+/// Like `scan_ports`, but every connection attempt holds a permit from `limit`, so one
+/// semaphore shared across hosts caps the connections open on the whole network.
+pub async fn scan_ports_limited(
+    ip: IpAddr,
+    ports: &[u16],
+    timeout: Duration,
+    limit: Arc<Semaphore>,
+) -> Vec<u16> {
+    let attempts = ports.iter().map(|&port| {
+        let limit = limit.clone();
+        async move {
+            let _permit = limit.acquire().await.ok()?;
+            match tokio::time::timeout(timeout, TcpStream::connect((ip, port))).await {
+                Ok(Ok(_)) => Some(port),
+                _ => None,
+            }
+        }
+    });
+
+    let mut open_ports: Vec<u16> = join_all(attempts).await.into_iter().flatten().collect();
+    open_ports.sort_unstable();
+    open_ports.dedup();
+    open_ports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, TcpListener};
+
+    #[tokio::test]
+    async fn listening_port_is_open_and_closed_port_is_not() {
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let listener = TcpListener::bind((localhost, 0)).unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let closed_port = {
+            let released = TcpListener::bind((localhost, 0)).unwrap();
+            released.local_addr().unwrap().port()
+        };
+
+        let open_ports = scan_ports(
+            localhost,
+            &[closed_port, open_port],
+            Duration::from_millis(500),
+        )
+        .await;
+
+        assert_eq!(open_ports, vec![open_port]);
+    }
+}
@@ -0,0 +1,236 @@
+use super::diff::diff_scans;
+use super::scan_devices;
+use super::types::{LocalNetworkDevice, ScanConfig, ScanControl};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+type DeviceCallback = Arc<dyn Fn(&LocalNetworkDevice) + Send + Sync>;
+type ScanFn =
+    Box<dyn Fn(ScanControl) -> BoxFuture<'static, Option<Vec<LocalNetworkDevice>>> + Send>;
+
+/// Rescans the network every `interval` and reports devices that appeared or disappeared
+/// since the previous scan. The first scan only establishes the baseline, and scans that
+/// fail are skipped rather than diffed.
+pub struct ScanScheduler {
+    scan: ScanFn,
+    interval: Duration,
+    on_added: Option<DeviceCallback>,
+    on_removed: Option<DeviceCallback>,
+}
+
+#[allow(dead_code)]
+impl ScanScheduler {
+    pub fn new(config: ScanConfig, interval: Duration) -> Self {
+        Self::with_scan(interval, move |control| {
+            let config = config.clone();
+            async move { scan_devices(&config, control, None).await }
+        })
+    }
+
+    /// Schedules `scan` instead of the network scan, e.g. to drive the scheduler from
+    /// recorded results. `scan` returns `None` when it failed.
+    pub fn with_scan<F, Fut>(interval: Duration, scan: F) -> Self
+    where
+        F: Fn(ScanControl) -> Fut + Send + 'static,
+        Fut: Future<Output = Option<Vec<LocalNetworkDevice>>> + Send + 'static,
+    {
+        Self {
+            scan: Box::new(move |control| scan(control).boxed()),
+            interval,
+            on_added: None,
+            on_removed: None,
+        }
+    }
+
+    pub fn on_added(
+        mut self,
+        callback: impl Fn(&LocalNetworkDevice) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_added = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn on_removed(
+        mut self,
+        callback: impl Fn(&LocalNetworkDevice) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_removed = Some(Arc::new(callback));
+        self
+    }
+
+    /// Starts scanning in the background until the returned handle is stopped.
+    pub fn start(self) -> ScanSchedulerHandle {
+        let cancellation = CancellationToken::new();
+        let task = tokio::spawn(self.run(cancellation.clone()));
+
+        ScanSchedulerHandle { cancellation, task }
+    }
+
+    async fn run(self, cancellation: CancellationToken) {
+        let mut ticker = tokio::time::interval(self.interval);
+        // A scan longer than the interval delays the next one instead of starting a burst.
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut previous: Option<Vec<LocalNetworkDevice>> = None;
+
+        loop {
+            tokio::select! {
+                _ = cancellation.cancelled() => break,
+                _ = ticker.tick() => {}
+            }
+
+            let control = ScanControl::with_cancellation(cancellation.clone());
+            let scan = tokio::select! {
+                _ = cancellation.cancelled() => break,
+                devices = (self.scan)(control) => devices,
+            };
+
+            // Diffing a failed scan (no gateway, no datalink privileges) would report every
+            // known device as removed, then as added again once scanning works.
+            let Some(current) = scan else {
+                continue;
+            };
+
+            if let Some(previous) = &previous {
+                let diff = diff_scans(previous, &current);
+                if let Some(on_added) = &self.on_added {
+                    diff.added.iter().for_each(|device| on_added(device));
+                }
+                if let Some(on_removed) = &self.on_removed {
+                    diff.removed.iter().for_each(|device| on_removed(device));
+                }
+            }
+
+            previous = Some(current);
+        }
+    }
+}
+
+pub struct ScanSchedulerHandle {
+    cancellation: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+#[allow(dead_code)]
+impl ScanSchedulerHandle {
+    /// Stops scheduling and cancels any scan in progress.
+    pub fn stop(&self) {
+        self.cancellation.cancel();
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Waits for the scheduler to wind down after `stop`.
+    pub async fn join(self) {
+        let _ = self.task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::network::scanner::types::test_device;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use tokio::sync::Notify;
+
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Feeds `scans` to a scheduler on the paused test clock and returns the MACs reported
+    /// as (added, removed) once every scan has been diffed.
+    async fn run_scans(scans: Vec<Option<Vec<LocalNetworkDevice>>>) -> (Vec<String>, Vec<String>) {
+        let scan_count = scans.len() as u32;
+        let scans = Arc::new(Mutex::new(VecDeque::from(scans)));
+        let exhausted = Arc::new(Notify::new());
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let removed = Arc::new(Mutex::new(Vec::new()));
+
+        let handle = ScanScheduler::with_scan(INTERVAL, {
+            let exhausted = exhausted.clone();
+            move |_control| {
+                let scan = scans.lock().unwrap().pop_front();
+                let exhausted = exhausted.clone();
+                async move {
+                    match scan {
+                        Some(scan) => scan,
+                        None => {
+                            // Every callback for the earlier scans has run by now
+                            exhausted.notify_one();
+                            std::future::pending().await
+                        }
+                    }
+                }
+            }
+        })
+        .on_added({
+            let added = added.clone();
+            move |device| added.lock().unwrap().push(device.mac_address.clone())
+        })
+        .on_removed({
+            let removed = removed.clone();
+            move |device| removed.lock().unwrap().push(device.mac_address.clone())
+        })
+        .start();
+
+        let start = tokio::time::Instant::now();
+        exhausted.notified().await;
+        // The first scan runs immediately, each later one a full interval after it
+        assert!(start.elapsed() >= INTERVAL * scan_count);
+        handle.stop();
+        handle.join().await;
+
+        let added = added.lock().unwrap().clone();
+        let removed = removed.lock().unwrap().clone();
+        (added, removed)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn device_that_appears_then_disappears_fires_both_callbacks() {
+        let router = test_device("192.168.1.1", "d8:be:65:00:00:01");
+        let phone = test_device("192.168.1.31", "cc:40:85:d1:4e:94");
+        let scans = vec![
+            Some(vec![router.clone()]),
+            Some(vec![router.clone(), phone.clone()]),
+            Some(vec![router.clone()]),
+        ];
+
+        let (added, removed) = run_scans(scans).await;
+
+        assert_eq!(added, vec![phone.mac_address.clone()]);
+        assert_eq!(removed, vec![phone.mac_address]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_scan_is_skipped() {
+        let router = test_device("192.168.1.1", "d8:be:65:00:00:01");
+        let phone = test_device("192.168.1.31", "cc:40:85:d1:4e:94");
+        let scans = vec![
+            Some(vec![router.clone(), phone.clone()]),
+            None,
+            Some(vec![router.clone(), phone.clone()]),
+        ];
+
+        let (added, removed) = run_scans(scans).await;
+
+        assert!(added.is_empty());
+        assert!(removed.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn empty_scan_reports_every_device_as_removed() {
+        let router = test_device("192.168.1.1", "d8:be:65:00:00:01");
+        let scans = vec![Some(vec![router.clone()]), Some(Vec::new())];
+
+        let (added, removed) = run_scans(scans).await;
+
+        assert!(added.is_empty());
+        assert_eq!(removed, vec![router.mac_address]);
+    }
+}
@@ -1,5 +1,11 @@
+use ipnet::IpNet;
 use pnet::datalink::NetworkInterface as PnetNetworkInterface;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Clone)]
 pub struct LocalNetworkInterface {
@@ -7,15 +13,240 @@ pub struct LocalNetworkInterface {
     pub pnet_interface_ref: Option<PnetNetworkInterface>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct LocalNetworkDevice {
     pub id: String,
     pub mac_address: String,
+    /// IPv4 address when the device has one, otherwise its IPv6 address.
     pub ip_address: String,
+    /// IPv6 address found via neighbor discovery, preferring a global one over link-local.
+    pub ipv6_address: Option<String>,
     pub hostname: Option<String>,
     pub device_name: Option<String>,
     pub vendor: Option<String>,
     pub mdns_service_types: Option<Vec<String>>,
+    /// Which of `DISCOVERY_PORTS` accepted a TCP connection; `None` when not probed.
+    pub open_ports: Option<Vec<u16>>,
+    pub device_class: DeviceClass,
+    pub signals: DeviceSignals,
+}
+
+/// A device with only its addresses filled in, for tests.
+#[cfg(test)]
+pub fn test_device(ip_address: &str, mac_address: &str) -> LocalNetworkDevice {
+    LocalNetworkDevice {
+        ip_address: ip_address.to_string(),
+        mac_address: mac_address.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Raw identifying information collected for a device, one field per discovery source.
+/// These are resolved into the final name/vendor/class by `fingerprint::fingerprint`.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceSignals {
+    pub oui_vendor: Option<String>,
+    pub reverse_dns_hostname: Option<String>,
+    pub mdns_name: Option<String>,
+    pub smart_device_name: Option<String>,
+    pub mapped_name: Option<String>,
+    pub mapped_device_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeviceClass {
+    Light,
+    Hub,
+    MediaPlayer,
+    Camera,
+    Computer,
+    NetworkEquipment,
+    #[default]
+    Unknown,
+}
+
+impl std::fmt::Display for DeviceClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceClass::Light => write!(f, "Light"),
+            DeviceClass::Hub => write!(f, "Hub"),
+            DeviceClass::MediaPlayer => write!(f, "Media Player"),
+            DeviceClass::Camera => write!(f, "Camera"),
+            DeviceClass::Computer => write!(f, "Computer"),
+            DeviceClass::NetworkEquipment => write!(f, "Network Equipment"),
+            DeviceClass::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Timeouts are written as milliseconds in `scan_config.json`; missing fields use the defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// How long to listen for ARP replies after the sweep starts.
+    #[serde(with = "duration_ms")]
+    pub arp_timeout: Duration,
+    /// How long to listen for IPv6 Neighbor Advertisements and echo replies.
+    #[serde(with = "duration_ms")]
+    pub ndp_timeout: Duration,
+    /// How long each mDNS service query listens for responses.
+    #[serde(with = "duration_ms")]
+    pub mdns_timeout: Duration,
+    /// Number of ARP requests sent per burst.
+    pub batch_size: usize,
+    /// Restricts the scan to this part of the interface network. Must be IPv4 and inside
+    /// the interface network; otherwise it is ignored and the whole network is scanned.
+    pub subnet_filter: Option<IpNet>,
+    /// Hosts that are never sent ARP requests or probed over HTTP/DNS.
+    pub exclude_ips: Vec<IpAddr>,
+    /// MAC prefixes (any case or separator, e.g. "cc:40:85"). Hosts the system ARP table
+    /// already maps to a matching MAC are never sent ARP requests; any other matching host is
+    /// dropped as soon as it replies. Neither is probed over HTTP/DNS.
+    pub exclude_mac_prefixes: Vec<String>,
+    /// Keep excluded hosts in the results when they are only known from the system ARP
+    /// table. They are still never probed.
+    pub include_excluded_passive: bool,
+    /// How long each TCP connect attempt of the port scan may take.
+    #[serde(with = "duration_ms")]
+    pub port_timeout: Duration,
+    /// Upper bound on port-scan connections open at once, across all devices.
+    pub max_port_connections: usize,
+    /// mDNS services queried in addition to `MDNS_SERVICES`, and how many run at once.
+    pub mdns: MdnsConfig,
+    /// IEEE OUI registry export (`oui.txt` or `oui.csv`) consulted for MAC prefixes the
+    /// built-in vendor table does not know.
+    pub oui_database: Option<String>,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            arp_timeout: Duration::from_millis(600),
+            ndp_timeout: Duration::from_millis(400),
+            mdns_timeout: Duration::from_millis(500),
+            batch_size: 10,
+            subnet_filter: None,
+            exclude_ips: Vec::new(),
+            exclude_mac_prefixes: Vec::new(),
+            include_excluded_passive: false,
+            port_timeout: Duration::from_millis(300),
+            max_port_connections: 64,
+            mdns: MdnsConfig::default(),
+            oui_database: None,
+        }
+    }
+}
+
+mod duration_ms {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+impl ScanConfig {
+    pub fn load_from_file(path: &str) -> Result<Self, std::io::Error> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// The range to sweep on `interface_network`, narrowed by `subnet_filter`. A filter that
+    /// cannot narrow it is rejected with a description of why.
+    pub fn scan_range(&self, interface_network: IpNet) -> Result<IpNet, String> {
+        let Some(filter) = self.subnet_filter else {
+            return Ok(interface_network);
+        };
+
+        if !matches!(filter, IpNet::V4(_)) {
+            return Err(format!("subnet_filter {} is not an IPv4 network", filter));
+        }
+        if !interface_network.contains(&filter) {
+            return Err(format!(
+                "subnet_filter {} is outside the interface network {}",
+                filter,
+                interface_network.trunc()
+            ));
+        }
+        Ok(filter)
+    }
+
+    pub fn is_ip_excluded(&self, ip: &IpAddr) -> bool {
+        self.exclude_ips.contains(ip)
+    }
+
+    pub fn is_mac_excluded(&self, mac: &str) -> bool {
+        let mac = normalize_mac(mac);
+        self.exclude_mac_prefixes.iter().any(|prefix| {
+            let prefix = normalize_mac(prefix);
+            !prefix.is_empty() && mac.starts_with(&prefix)
+        })
+    }
+
+    /// Checks both addresses of a dual-stack device, as well as its MAC.
+    pub fn is_device_excluded(&self, device: &LocalNetworkDevice) -> bool {
+        let ip_excluded = std::iter::once(&device.ip_address)
+            .chain(device.ipv6_address.as_ref())
+            .filter_map(|ip| ip.parse::<IpAddr>().ok())
+            .any(|ip| self.is_ip_excluded(&ip));
+        ip_excluded || self.is_mac_excluded(&device.mac_address)
+    }
+
+    /// Whether a device belongs in the results. Excluded devices are only kept when they
+    /// were `seen_passively` (read from the system ARP table) and `include_excluded_passive`
+    /// is set.
+    pub fn keeps_device(&self, device: &LocalNetworkDevice, seen_passively: bool) -> bool {
+        !self.is_device_excluded(device) || (seen_passively && self.include_excluded_passive)
+    }
+}
+
+pub fn normalize_mac(mac: &str) -> String {
+    mac.replace([':', '-', '.'], "").to_uppercase()
+}
+
+/// Shared handle for suspending or stopping an in-progress scan. Clones control the same scan.
+#[derive(Debug, Clone, Default)]
+pub struct ScanControl {
+    paused: Arc<AtomicBool>,
+    cancellation: CancellationToken,
+}
+
+#[allow(dead_code)]
+impl ScanControl {
+    pub fn with_cancellation(cancellation: CancellationToken) -> Self {
+        Self {
+            paused: Arc::default(),
+            cancellation,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
 }
 
 #[derive(Debug)]
@@ -31,6 +262,46 @@ pub const MDNS_SERVICES: &[&str] = &[
     "_workstation._tcp.local",
 ];
 
+/// Ports probed on each device after discovery: web/UPnP, SSH, RTSP cameras,
+/// Sonos, Chromecast and TP-Link Kasa.
+pub const DISCOVERY_PORTS: &[u16] = &[22, 80, 443, 554, 1400, 8008, 8009, 8080, 9999];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MdnsConfig {
+    #[serde(default)]
+    pub extra_services: Vec<String>,
+    /// How many service queries may run at once; each one holds its own socket.
+    #[serde(default = "default_max_concurrent_queries")]
+    pub max_concurrent_queries: usize,
+}
+
+fn default_max_concurrent_queries() -> usize {
+    8
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        Self {
+            extra_services: Vec::new(),
+            max_concurrent_queries: default_max_concurrent_queries(),
+        }
+    }
+}
+
+impl MdnsConfig {
+    /// Default `MDNS_SERVICES` followed by any configured extras, without duplicates.
+    pub fn services(&self) -> Vec<String> {
+        let mut services: Vec<String> = MDNS_SERVICES.iter().map(|s| s.to_string()).collect();
+        for service in &self.extra_services {
+            let service = service.trim();
+            if !service.is_empty() && !services.iter().any(|s| s == service) {
+                services.push(service.to_string());
+            }
+        }
+        services
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceConfig {
     pub mac_address: String,
@@ -56,12 +327,129 @@ impl DeviceMapping {
         }
     }
 
+    pub fn get_device(&self, mac_address: &str) -> Option<&DeviceConfig> {
+        self.devices
+            .iter()
+            .find(|device| device.mac_address.to_lowercase() == mac_address.to_lowercase())
+    }
+
     pub fn get_device_name(&self, mac_address: &str) -> Option<String> {
-        for device in &self.devices {
-            if device.mac_address.to_lowercase() == mac_address.to_lowercase() {
-                return Some(format!("{} ({})", device.device_name, device.room));
-            }
-        }
-        None
+        self.get_device(mac_address)
+            .map(|device| format!("{} ({})", device.device_name, device.room))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subnet_filter_narrows_the_interface_network() {
+        let interface_network: IpNet = "10.0.0.5/16".parse().unwrap();
+        let filtered = |filter: &str| ScanConfig {
+            subnet_filter: Some(filter.parse().unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            ScanConfig::default().scan_range(interface_network),
+            Ok(interface_network)
+        );
+        assert_eq!(
+            filtered("10.0.3.0/24").scan_range(interface_network),
+            Ok("10.0.3.0/24".parse().unwrap())
+        );
+        assert!(
+            filtered("10.0.0.0/8")
+                .scan_range(interface_network)
+                .is_err()
+        );
+        assert!(
+            filtered("192.168.1.0/24")
+                .scan_range(interface_network)
+                .is_err()
+        );
+        assert!(filtered("fd00::/64").scan_range(interface_network).is_err());
+    }
+
+    #[test]
+    fn mac_prefixes_match_regardless_of_case_and_separator() {
+        let config = ScanConfig {
+            exclude_mac_prefixes: vec!["cc-40-85".to_string(), "  ".to_string()],
+            ..Default::default()
+        };
+
+        assert!(config.is_mac_excluded("CC:40:85:d1:4e:94"));
+        assert!(config.is_mac_excluded("cc40.85d1.4e94"));
+        assert!(!config.is_mac_excluded("cc:40:86:d1:4e:94"));
+    }
+
+    #[test]
+    fn excluding_the_ipv6_address_excludes_a_dual_stack_device() {
+        let config = ScanConfig {
+            exclude_ips: vec!["fe80::1cc0:3eff:fe12:3456".parse().unwrap()],
+            ..Default::default()
+        };
+        let mut dual_stack = test_device("192.168.1.40", "1e:c0:3e:12:34:56");
+        assert!(!config.is_device_excluded(&dual_stack));
+
+        dual_stack.ipv6_address = Some("fe80::1cc0:3eff:fe12:3456".to_string());
+        assert!(config.is_device_excluded(&dual_stack));
+    }
+
+    #[test]
+    fn excluded_devices_are_dropped_unless_seen_passively_and_requested() {
+        let excluded = test_device("192.168.1.31", "cc:40:85:d1:4e:94");
+        let other = test_device("192.168.1.32", "d8:be:65:00:00:01");
+        let mut config = ScanConfig {
+            exclude_mac_prefixes: vec!["cc:40:85".to_string()],
+            ..Default::default()
+        };
+
+        assert!(!config.keeps_device(&excluded, false));
+        assert!(!config.keeps_device(&excluded, true));
+        assert!(config.keeps_device(&other, false));
+
+        config.include_excluded_passive = true;
+        assert!(config.keeps_device(&excluded, true));
+        // Hosts that answered the active sweep are never kept.
+        assert!(!config.keeps_device(&excluded, false));
+    }
+
+    #[test]
+    fn extra_mdns_services_are_queried_alongside_the_defaults() {
+        let config = MdnsConfig {
+            extra_services: vec![
+                " _hue._tcp.local ".to_string(),
+                "_airplay._tcp.local".to_string(),
+                String::new(),
+            ],
+            ..Default::default()
+        };
+
+        let services = config.services();
+
+        assert_eq!(services.len(), MDNS_SERVICES.len() + 1);
+        assert!(
+            MDNS_SERVICES
+                .iter()
+                .all(|default| services.iter().any(|s| s == default))
+        );
+        assert_eq!(services.last().map(String::as_str), Some("_hue._tcp.local"));
+    }
+
+    #[test]
+    fn mdns_settings_are_read_from_the_scan_config() {
+        let config: ScanConfig = serde_json::from_str(
+            r#"{ "mdns": { "extra_services": ["_hue._tcp.local"], "max_concurrent_queries": 2 } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.mdns.extra_services,
+            vec!["_hue._tcp.local".to_string()]
+        );
+        assert_eq!(config.mdns.max_concurrent_queries, 2);
+        assert_eq!(config.arp_timeout, ScanConfig::default().arp_timeout);
     }
 }
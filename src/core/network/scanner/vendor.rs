@@ -1,9 +1,13 @@
+use super::types::normalize_mac;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VendorMapping {
     pub mappings: HashMap<String, String>,
+    /// Consulted only for prefixes the built-in table does not know.
+    #[serde(skip)]
+    pub database: VendorDb,
 }
 
 impl VendorMapping {
@@ -15,18 +19,32 @@ impl VendorMapping {
         }
     }
 
+    pub fn with_database(mut self, database: VendorDb) -> Self {
+        self.database = database;
+        self
+    }
+
+    /// Looks the MAC up in the user mappings, then the built-in table, then the OUI database.
+    /// The built-in names come first because bulb control and device classification match
+    /// on them (e.g. "Philips Hue", "HomeMATE"), where the registry has company names.
     pub fn get_vendor(&self, mac: &str) -> Option<String> {
-        let oui_prefix = mac.replace(":", "").replace("-", "").to_uppercase();
-        if oui_prefix.len() < 6 {
-            return None;
-        }
-        let oui_prefix = &oui_prefix[0..6];
+        let oui_prefix = oui_prefix(mac)?;
 
-        if let Some(vendor) = self.mappings.get(oui_prefix) {
+        if let Some(vendor) = self.mappings.get(&oui_prefix) {
             return Some(vendor.clone());
         }
 
-        get_default_vendor_mapping(oui_prefix)
+        if let Some(vendor) = get_default_vendor_mapping(&oui_prefix) {
+            return Some(vendor);
+        }
+
+        if is_locally_administered(&oui_prefix) {
+            return Some("Local Admin".to_string());
+        }
+
+        self.database
+            .lookup(mac)
+            .or_else(|| Some("Unknown Vendor".to_string()))
     }
 }
 
@@ -34,14 +52,88 @@ impl Default for VendorMapping {
     fn default() -> Self {
         Self {
             mappings: HashMap::new(),
+            database: VendorDb::default(),
         }
     }
 }
 
-pub fn get_vendor_from_mac(mac: &str) -> Option<String> {
-    let vendor_mapping = VendorMapping::load_from_file("vendor_config.json").unwrap_or_default();
+/// OUI prefix to vendor table parsed from an IEEE registry export, either the `oui.txt`
+/// listing or the `oui.csv` (MA-L) file.
+#[derive(Debug, Clone, Default)]
+pub struct VendorDb {
+    vendors: HashMap<String, String>,
+}
+
+impl VendorDb {
+    /// Unlike the JSON configs, a missing file is an error: the path was given explicitly.
+    pub fn load_from_file(path: &str) -> Result<Self, std::io::Error> {
+        std::fs::read_to_string(path).map(|content| Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let vendors = content
+            .lines()
+            .filter_map(|line| parse_txt_line(line).or_else(|| parse_csv_line(line)))
+            .collect();
+
+        Self { vendors }
+    }
+
+    pub fn lookup(&self, mac: &str) -> Option<String> {
+        self.vendors.get(&oui_prefix(mac)?).cloned()
+    }
+}
+
+/// `00-22-72   (hex)\t\tAmerican Micro-Fuel Device Corp.`
+fn parse_txt_line(line: &str) -> Option<(String, String)> {
+    let (prefix, vendor) = line.split_once("(hex)")?;
+    let prefix = oui_prefix(prefix.trim())?;
+    let vendor = vendor.trim();
+    (!vendor.is_empty()).then(|| (prefix, vendor.to_string()))
+}
 
-    vendor_mapping.get_vendor(mac)
+/// `MA-L,002272,American Micro-Fuel Device Corp.,"Address, City"`
+fn parse_csv_line(line: &str) -> Option<(String, String)> {
+    let fields = split_csv_line(line);
+    let (prefix, vendor) = (fields.get(1)?.trim(), fields.get(2)?.trim());
+    if prefix.len() != 6 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) || vendor.is_empty() {
+        return None;
+    }
+    Some((prefix.to_uppercase(), vendor.to_string()))
+}
+
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+/// First three octets of a MAC as uppercase hex, whatever its case or separators.
+fn oui_prefix(mac: &str) -> Option<String> {
+    let normalized = normalize_mac(mac);
+    normalized.get(0..6).map(|prefix| prefix.to_string())
+}
+
+fn is_locally_administered(oui_prefix: &str) -> bool {
+    ["02", "06", "0A", "0E"]
+        .iter()
+        .any(|prefix| oui_prefix.starts_with(prefix))
 }
 
 fn get_default_vendor_mapping(oui_prefix: &str) -> Option<String> {
@@ -143,16 +235,81 @@ fn get_default_vendor_mapping(oui_prefix: &str) -> Option<String> {
         "00D0C9" => Some("Intel".to_string()),
         "001A92" => Some("ASIX Electronics".to_string()),
         "00409D" => Some("Brocade Communications".to_string()),
-        _ => {
-            if oui_prefix.starts_with("02")
-                || oui_prefix.starts_with("06")
-                || oui_prefix.starts_with("0A")
-                || oui_prefix.starts_with("0E")
-            {
-                Some("Local Admin".to_string())
-            } else {
-                Some("Unknown Vendor".to_string())
-            }
-        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "\
+OUI/MA-L                                                    Organization
+company_id                                                  Organization
+                                                            Address
+
+00-22-72   (hex)\t\tAmerican Micro-Fuel Device Corp.
+002272     (base 16)\t\tAmerican Micro-Fuel Device Corp.
+\t\t\t\t2181 Buchanan Loop
+\t\t\t\tFerndale  WA  98248
+\t\t\t\tUS
+
+CC-40-85   (hex)\t\tWuXi Example Co., Ltd.
+";
+
+    #[test]
+    fn loads_an_oui_txt_file_and_resolves_macs() {
+        let path = std::env::temp_dir().join(format!("exia-oui-{}.txt", std::process::id()));
+        std::fs::write(&path, FIXTURE).unwrap();
+        let database = VendorDb::load_from_file(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        let database = database.unwrap();
+
+        assert_eq!(
+            database.lookup("00:22:72:ab:cd:ef").as_deref(),
+            Some("American Micro-Fuel Device Corp.")
+        );
+        assert_eq!(
+            database.lookup("0022.72AB.CDEF").as_deref(),
+            Some("American Micro-Fuel Device Corp.")
+        );
+        assert_eq!(database.lookup("00:22:73:ab:cd:ef"), None);
+    }
+
+    #[test]
+    fn parses_the_csv_registry_format() {
+        let database = VendorDb::parse(
+            "Registry,Assignment,Organization Name,Organization Address\n\
+             MA-L,002272,\"Micro-Fuel, Inc.\",\"2181 Buchanan Loop, Ferndale\"",
+        );
+
+        assert_eq!(
+            database.lookup("00-22-72-01-02-03").as_deref(),
+            Some("Micro-Fuel, Inc.")
+        );
+    }
+
+    #[test]
+    fn built_in_names_take_precedence_over_the_database() {
+        let vendors = VendorMapping::default().with_database(VendorDb::parse(FIXTURE));
+
+        // Known to the built-in table: keeps the name bulb control matches on.
+        assert_eq!(
+            vendors.get_vendor("cc:40:85:d1:4e:94").as_deref(),
+            Some("Philips Hue/Smart Lighting")
+        );
+        // Unknown to the built-in table: resolved from the database.
+        assert_eq!(
+            vendors.get_vendor("00:22:72:ab:cd:ef").as_deref(),
+            Some("American Micro-Fuel Device Corp.")
+        );
+        assert_eq!(
+            vendors.get_vendor("00:22:73:ab:cd:ef").as_deref(),
+            Some("Unknown Vendor")
+        );
+        assert_eq!(
+            vendors.get_vendor("0a:11:22:33:44:55").as_deref(),
+            Some("Local Admin")
+        );
     }
 }
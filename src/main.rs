@@ -1,14 +1,33 @@
 mod core;
+mod spinner;
 
-use crate::core::network::scanner::{bulb_control, scan_local_network_devices};
+use crate::core::logger::{get_error_logger, LogType};
+use crate::core::network::scanner::{bulb_control, scan_local_network_devices, types::ScanConfig};
+use crate::spinner::Spinner;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("🔍 Starting network scan for devices...");
-    let devices = scan_local_network_devices().await;
+    // A malformed config must not silently drop the exclusion lists
+    let scan_config = match ScanConfig::load_from_file("scan_config.json") {
+        Ok(scan_config) => scan_config,
+        Err(e) => {
+            eprintln!("❌ Invalid scan_config.json: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let spinner = Spinner::from_env().map(|spinner| spinner.start("Scanning network..."));
+    let devices = scan_local_network_devices(&scan_config).await;
+    if let Some(spinner) = spinner {
+        spinner.stop().await;
+    }
 
     if devices.is_empty() {
         println!("❌ No devices found on the network");
+        let log_path = get_error_logger().current_log_path(LogType::NetworkScanner);
+        if log_path.exists() {
+            println!("📄 Scanner errors were logged to {}", log_path.display());
+        }
         return Ok(());
     }
 
@@ -27,13 +46,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         };
 
         println!(
-            "{} Device: {} | IP: {} | MAC: {} | Vendor: {} | Name: {}",
+            "{} Device: {} | IP: {} | MAC: {} | Vendor: {} | Name: {} | Class: {}",
             emoji,
             device.id,
             device.ip_address,
             device.mac_address,
             device.vendor.as_ref().unwrap_or(&"Unknown".to_string()),
-            device.device_name.as_ref().unwrap_or(&"None".to_string())
+            device.device_name.as_ref().unwrap_or(&"None".to_string()),
+            device.device_class
         );
     }
 
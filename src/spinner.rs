@@ -0,0 +1,93 @@
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+const DOTS: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const LINE: &[&str] = &["|", "/", "-", "\\"];
+
+pub struct Spinner {
+    frames: &'static [&'static str],
+    interval: Duration,
+}
+
+impl Spinner {
+    /// Reads `EXIA_SPINNER` ("dots", "line" or "off"). Returns `None` when the spinner is
+    /// off or stdout is not a terminal, so piped output stays clean.
+    pub fn from_env() -> Option<Self> {
+        if !std::io::stdout().is_terminal() {
+            return None;
+        }
+
+        let frames = match std::env::var("EXIA_SPINNER").as_deref() {
+            Ok("off") => return None,
+            Ok("line") => LINE,
+            _ => DOTS,
+        };
+
+        Some(Self {
+            frames,
+            interval: Duration::from_millis(100),
+        })
+    }
+
+    /// The line shown once `elapsed` has passed; the frame advances once per interval.
+    pub fn frame(&self, message: &str, elapsed: Duration) -> String {
+        let tick = (elapsed.as_millis() / self.interval.as_millis().max(1)) as usize;
+        format!(
+            "{} {} {:.1}s",
+            self.frames[tick % self.frames.len()],
+            message,
+            elapsed.as_secs_f32()
+        )
+    }
+
+    pub fn start(self, message: &str) -> SpinnerHandle {
+        let message = message.to_string();
+        let task = tokio::spawn(async move {
+            let start_time = Instant::now();
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                eprint!("\r{}", self.frame(&message, start_time.elapsed()));
+                let _ = std::io::stderr().flush();
+            }
+        });
+
+        SpinnerHandle { task }
+    }
+}
+
+pub struct SpinnerHandle {
+    task: JoinHandle<()>,
+}
+
+impl SpinnerHandle {
+    /// Stops the spinner and clears its line so results print on a clean row. The task is
+    /// awaited first, so a frame being drawn on another worker cannot land after the clear.
+    pub async fn stop(self) {
+        self.task.abort();
+        let _ = self.task.await;
+        eprint!("\r\x1b[2K");
+        let _ = std::io::stderr().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_advances_once_per_interval_and_wraps() {
+        let spinner = Spinner {
+            frames: LINE,
+            interval: Duration::from_millis(100),
+        };
+        let frame_at = |millis| spinner.frame("Scanning", Duration::from_millis(millis));
+
+        assert_eq!(frame_at(0), "| Scanning 0.0s");
+        assert_eq!(frame_at(120), "/ Scanning 0.1s");
+        assert_eq!(frame_at(200), "- Scanning 0.2s");
+        assert_eq!(frame_at(300), "\\ Scanning 0.3s");
+        assert_eq!(frame_at(400), "| Scanning 0.4s");
+    }
+}